@@ -1,27 +1,130 @@
 use proc_macro2::Span;
+use quote::ToTokens;
 use syn::punctuated::Punctuated;
-use syn::{Expr, ExprLit, Lit, LitStr, Meta, MetaNameValue, Token};
+use syn::{Expr, ExprLit, Lit, LitBool, LitInt, LitStr, Meta, MetaList, MetaNameValue, Token};
+
+/// Hidden argument naming a `cfg_attr` predicate that holds.
+pub(crate) const CFG_ACTIVE: &str = "__cfg_active";
+
+/// Hidden argument naming a `cfg_attr` predicate that does not hold.
+pub(crate) const CFG_INACTIVE: &str = "__cfg_inactive";
 
 pub(crate) struct MacroArgs {
-    pub(crate) input: TemplateInput,
+    /// Missing on an enum whose variants carry their own templates, and for
+    /// `in_doc` items until the documentation is read.
+    pub(crate) input: Option<TemplateInput>,
+    /// `in_doc = true`: the template is a code block in the item's docs.
+    pub(crate) in_doc: Option<LitBool>,
     pub(crate) ext: Option<LitStr>,
+    /// Askama's `config` argument, read here and still forwarded.
+    pub(crate) config: Option<LitStr>,
+    /// Askama's `syntax` argument, read here and still forwarded.
+    pub(crate) syntax: Option<LitStr>,
+    /// Askama's `whitespace` argument, read here and still forwarded.
+    pub(crate) whitespace: Option<LitStr>,
+    pub(crate) flatten_extends: bool,
+    pub(crate) strip_block_whitespace: bool,
+    /// Removes whitespace next to tags of block-level elements.
+    pub(crate) remove_block_whitespace: bool,
+    /// Leaves out start and end tags that HTML lets browsers imply.
+    pub(crate) omit_optional_tags: bool,
+    /// Drops attribute value quotes where HTML allows it.
+    pub(crate) remove_attribute_quotes: bool,
+    /// Writes boolean attributes with their name alone.
+    pub(crate) collapse_boolean_attributes: bool,
+    /// Drops attributes set to their default value.
+    pub(crate) remove_redundant_attributes: bool,
+    /// Keeps every line break so Askama's errors point at the original lines.
+    pub(crate) preserve_lines: bool,
+    /// Turns minifier warnings into errors.
+    pub(crate) deny_warnings: bool,
+    /// Size budget of the minified template in bytes.
+    pub(crate) max_size: Option<LitInt>,
+    /// `metadata = true`: generates associated constants describing the
+    /// minified template of the item.
+    pub(crate) metadata: Option<LitBool>,
+    /// Shows the minified source at compile time.
+    pub(crate) debug: Option<DebugOutput>,
+    /// `cfg_attr` predicates that an outer expansion already branched on,
+    /// with whether they hold. Set through the hidden `__cfg_active(...)`
+    /// and `__cfg_inactive(...)` arguments.
+    pub(crate) cfgs: Vec<(String, bool)>,
     pub(crate) passthrough: Vec<Meta>,
 }
 
+#[derive(Clone)]
 pub(crate) enum TemplateInput {
     Path(LitStr),
     Source(LitStr),
+    /// Template read from the docs, spanned on the first doc comment.
+    Doc(LitStr),
+}
+
+/// What `debug = "..."` shows in its compile-time note.
+#[derive(Clone, Copy)]
+pub(crate) enum DebugOutput {
+    /// The minified source and the byte savings.
+    Minified,
+    /// The original and the minified source and the byte savings.
+    Diff,
+}
+
+impl TemplateInput {
+    /// The literal that errors and warnings about the template point at.
+    pub(crate) fn literal(&self) -> &LitStr {
+        match self {
+            Self::Path(literal) | Self::Source(literal) | Self::Doc(literal) => literal,
+        }
+    }
 }
 
 impl MacroArgs {
     pub(crate) fn parse(input: syn::parse::ParseStream<'_>) -> syn::Result<Self> {
+        Self::parse_with_defaults(input, None)
+    }
+
+    /// Parses the arguments of a `#[template_minify(...)]` enum variant or of
+    /// an existing `#[template(...)]`. The outer arguments provide defaults
+    /// for the settings that Askama also inherits, plus the minifier options.
+    pub(crate) fn parse_nested(list: &MetaList, defaults: &Self) -> syn::Result<Self> {
+        list.parse_args_with(|input: syn::parse::ParseStream<'_>| {
+            Self::parse_with_defaults(input, Some(defaults))
+        })
+    }
+
+    fn parse_with_defaults(
+        input: syn::parse::ParseStream<'_>,
+        defaults: Option<&Self>,
+    ) -> syn::Result<Self> {
         let metas = Punctuated::<Meta, Token![,]>::parse_terminated(input)?;
         let mut path = None;
         let mut source = None;
+        let mut in_doc = None;
         let mut ext = None;
+        let mut config = None;
+        let mut syntax = None;
+        let mut whitespace = None;
+        let mut flatten_extends = None;
+        let mut strip_block_whitespace = None;
+        let mut remove_block_whitespace = None;
+        let mut omit_optional_tags = None;
+        let mut remove_attribute_quotes = None;
+        let mut collapse_boolean_attributes = None;
+        let mut remove_redundant_attributes = None;
+        let mut preserve_lines = None;
+        let mut deny_warnings = None;
+        let mut debug = None;
+        let mut max_size = None;
+        let mut metadata = None;
+        let mut cfgs = defaults.map_or_else(Vec::new, |defaults| defaults.cfgs.clone());
         let mut passthrough = Vec::new();
 
         for meta in metas {
+            if let Some(cfg) = cfg_state(&meta)? {
+                cfgs.push(cfg);
+                continue;
+            }
+
             if let Some(value) = string_name_value(&meta, "path")? {
                 set_once(&mut path, value, "duplicate `path` argument")?;
                 continue;
@@ -32,37 +135,231 @@ impl MacroArgs {
                 continue;
             }
 
+            if let Some(value) = bool_name_value(&meta, "in_doc")? {
+                set_once(&mut in_doc, value, "duplicate `in_doc` argument")?;
+                continue;
+            }
+
             if let Some(value) = string_name_value(&meta, "ext")? {
                 set_once(&mut ext, value, "duplicate `ext` argument")?;
                 continue;
             }
 
+            if let Some(value) = bool_name_value(&meta, "flatten_extends")? {
+                set_once(
+                    &mut flatten_extends,
+                    value,
+                    "duplicate `flatten_extends` argument",
+                )?;
+                continue;
+            }
+
+            if let Some(value) = bool_name_value(&meta, "strip_block_whitespace")? {
+                set_once(
+                    &mut strip_block_whitespace,
+                    value,
+                    "duplicate `strip_block_whitespace` argument",
+                )?;
+                continue;
+            }
+
+            if let Some(value) = bool_name_value(&meta, "remove_block_whitespace")? {
+                set_once(
+                    &mut remove_block_whitespace,
+                    value,
+                    "duplicate `remove_block_whitespace` argument",
+                )?;
+                continue;
+            }
+
+            if let Some(value) = bool_name_value(&meta, "omit_optional_tags")? {
+                set_once(
+                    &mut omit_optional_tags,
+                    value,
+                    "duplicate `omit_optional_tags` argument",
+                )?;
+                continue;
+            }
+
+            if let Some(value) = bool_name_value(&meta, "remove_attribute_quotes")? {
+                set_once(
+                    &mut remove_attribute_quotes,
+                    value,
+                    "duplicate `remove_attribute_quotes` argument",
+                )?;
+                continue;
+            }
+
+            if let Some(value) = bool_name_value(&meta, "collapse_boolean_attributes")? {
+                set_once(
+                    &mut collapse_boolean_attributes,
+                    value,
+                    "duplicate `collapse_boolean_attributes` argument",
+                )?;
+                continue;
+            }
+
+            if let Some(value) = bool_name_value(&meta, "remove_redundant_attributes")? {
+                set_once(
+                    &mut remove_redundant_attributes,
+                    value,
+                    "duplicate `remove_redundant_attributes` argument",
+                )?;
+                continue;
+            }
+
+            if let Some(value) = bool_name_value(&meta, "deny_warnings")? {
+                set_once(
+                    &mut deny_warnings,
+                    value,
+                    "duplicate `deny_warnings` argument",
+                )?;
+                continue;
+            }
+
+            if let Some(value) = bool_name_value(&meta, "metadata")? {
+                set_once(&mut metadata, value, "duplicate `metadata` argument")?;
+                continue;
+            }
+
+            if let Some(value) = int_name_value(&meta, "max_size")? {
+                set_once(&mut max_size, value, "duplicate `max_size` argument")?;
+                continue;
+            }
+
+            if let Some(value) = string_name_value(&meta, "debug")? {
+                let output = match value.value().as_str() {
+                    "minified" => DebugOutput::Minified,
+                    "diff" => DebugOutput::Diff,
+                    _ => {
+                        return Err(syn::Error::new_spanned(
+                            value,
+                            "`debug` must be `\"minified\"` or `\"diff\"`",
+                        ));
+                    }
+                };
+                set_once(&mut debug, output, "duplicate `debug` argument")?;
+                continue;
+            }
+
+            if let Some(value) = preserve_lines_name_value(&meta)? {
+                set_once(
+                    &mut preserve_lines,
+                    value,
+                    "duplicate `preserve_lines` argument",
+                )?;
+                continue;
+            }
+
+            if let Some(value) = string_name_value(&meta, "config")? {
+                set_once(&mut config, value, "duplicate `config` argument")?;
+            }
+
+            if let Some(value) = string_name_value(&meta, "syntax")? {
+                set_once(&mut syntax, value, "duplicate `syntax` argument")?;
+            }
+
+            if let Some(value) = string_name_value(&meta, "whitespace")? {
+                set_once(&mut whitespace, value, "duplicate `whitespace` argument")?;
+            }
+
             passthrough.push(meta);
         }
 
+        let in_doc = in_doc.filter(|value| value.value);
+        if let Some(in_doc) = in_doc
+            .as_ref()
+            .filter(|_| path.is_some() || source.is_some())
+        {
+            return Err(syn::Error::new_spanned(
+                in_doc,
+                "`in_doc` cannot be used together with `path` or `source`",
+            ));
+        }
+
         let input = match (path, source) {
-            (Some(path), None) => TemplateInput::Path(path),
-            (None, Some(source)) => TemplateInput::Source(source),
+            (Some(path), None) => Some(TemplateInput::Path(path)),
+            (None, Some(source)) => Some(TemplateInput::Source(source)),
             (Some(path), Some(_)) => {
                 return Err(syn::Error::new_spanned(
                     path,
                     "`path` and `source` cannot be used together",
                 ));
             }
-            (None, None) => {
-                return Err(syn::Error::new(
-                    Span::call_site(),
-                    "expected `path = \"...\"` or `source = \"...\"`",
-                ));
-            }
+            (None, None) => None,
+        };
+
+        let inherited = |value: Option<LitStr>, default: fn(&Self) -> &Option<LitStr>| {
+            value.or_else(|| defaults.and_then(|defaults| default(defaults).clone()))
+        };
+        let inherited_bool = |value: Option<LitBool>, default: fn(&Self) -> bool| {
+            value.map_or_else(|| defaults.is_some_and(default), |value| value.value)
         };
 
         Ok(Self {
             input,
-            ext,
+            in_doc,
+            ext: inherited(ext, |defaults| &defaults.ext),
+            config: inherited(config, |defaults| &defaults.config),
+            syntax: inherited(syntax, |defaults| &defaults.syntax),
+            whitespace: inherited(whitespace, |defaults| &defaults.whitespace),
+            flatten_extends: inherited_bool(flatten_extends, |defaults| defaults.flatten_extends),
+            strip_block_whitespace: inherited_bool(strip_block_whitespace, |defaults| {
+                defaults.strip_block_whitespace
+            }),
+            remove_block_whitespace: inherited_bool(remove_block_whitespace, |defaults| {
+                defaults.remove_block_whitespace
+            }),
+            omit_optional_tags: inherited_bool(omit_optional_tags, |defaults| {
+                defaults.omit_optional_tags
+            }),
+            remove_attribute_quotes: inherited_bool(remove_attribute_quotes, |defaults| {
+                defaults.remove_attribute_quotes
+            }),
+            collapse_boolean_attributes: inherited_bool(collapse_boolean_attributes, |defaults| {
+                defaults.collapse_boolean_attributes
+            }),
+            remove_redundant_attributes: inherited_bool(remove_redundant_attributes, |defaults| {
+                defaults.remove_redundant_attributes
+            }),
+            metadata: metadata.filter(|value| value.value),
+            max_size: max_size.or_else(|| defaults.and_then(|defaults| defaults.max_size.clone())),
+            debug: debug.or_else(|| defaults.and_then(|defaults| defaults.debug)),
+            deny_warnings: inherited_bool(deny_warnings, |defaults| defaults.deny_warnings),
+            preserve_lines: preserve_lines
+                .unwrap_or_else(|| defaults.is_some_and(|defaults| defaults.preserve_lines)),
+            cfgs,
             passthrough,
         })
     }
+
+    /// Whether the `cfg_attr` `predicate` holds, when an outer expansion
+    /// branched on it.
+    pub(crate) fn cfg_holds(&self, predicate: &Meta) -> Option<bool> {
+        let predicate = predicate.to_token_stream().to_string();
+        self.cfgs
+            .iter()
+            .find(|(known, _)| *known == predicate)
+            .map(|&(_, holds)| holds)
+    }
+}
+
+/// Reads a hidden `__cfg_active(predicate)` or `__cfg_inactive(predicate)`
+/// argument.
+fn cfg_state(meta: &Meta) -> syn::Result<Option<(String, bool)>> {
+    let Meta::List(list) = meta else {
+        return Ok(None);
+    };
+    let holds = if list.path.is_ident(CFG_ACTIVE) {
+        true
+    } else if list.path.is_ident(CFG_INACTIVE) {
+        false
+    } else {
+        return Ok(None);
+    };
+
+    let predicate: Meta = list.parse_args()?;
+    Ok(Some((predicate.to_token_stream().to_string(), holds)))
 }
 
 fn string_name_value(meta: &Meta, name: &str) -> syn::Result<Option<LitStr>> {
@@ -86,6 +383,76 @@ fn string_name_value(meta: &Meta, name: &str) -> syn::Result<Option<LitStr>> {
     }
 }
 
+fn bool_name_value(meta: &Meta, name: &str) -> syn::Result<Option<LitBool>> {
+    let Meta::NameValue(MetaNameValue { path, value, .. }) = meta else {
+        return Ok(None);
+    };
+
+    if !path.is_ident(name) {
+        return Ok(None);
+    }
+
+    match value {
+        Expr::Lit(ExprLit {
+            lit: Lit::Bool(value),
+            ..
+        }) => Ok(Some(value.clone())),
+        _ => Err(syn::Error::new_spanned(
+            value,
+            format!("`{name}` must be `true` or `false`"),
+        )),
+    }
+}
+
+fn int_name_value(meta: &Meta, name: &str) -> syn::Result<Option<LitInt>> {
+    let Meta::NameValue(MetaNameValue { path, value, .. }) = meta else {
+        return Ok(None);
+    };
+
+    if !path.is_ident(name) {
+        return Ok(None);
+    }
+
+    match value {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(value),
+            ..
+        }) => Ok(Some(value.clone())),
+        _ => Err(syn::Error::new_spanned(
+            value,
+            format!("`{name}` must be an integer literal"),
+        )),
+    }
+}
+
+/// Reads `preserve_lines = true`, or `preserve_lines = "debug"` to preserve
+/// lines only when the macro itself is built with debug assertions, as in
+/// Cargo's `dev` profile.
+fn preserve_lines_name_value(meta: &Meta) -> syn::Result<Option<bool>> {
+    let Meta::NameValue(MetaNameValue { path, value, .. }) = meta else {
+        return Ok(None);
+    };
+
+    if !path.is_ident("preserve_lines") {
+        return Ok(None);
+    }
+
+    match value {
+        Expr::Lit(ExprLit {
+            lit: Lit::Bool(value),
+            ..
+        }) => Ok(Some(value.value)),
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(value),
+            ..
+        }) if value.value() == "debug" => Ok(Some(cfg!(debug_assertions))),
+        _ => Err(syn::Error::new_spanned(
+            value,
+            "`preserve_lines` must be `true`, `false`, or `\"debug\"`",
+        )),
+    }
+}
+
 fn set_once<T>(target: &mut Option<T>, value: T, message: &str) -> syn::Result<()> {
     if target.is_some() {
         return Err(syn::Error::new(Span::call_site(), message));
@@ -0,0 +1,228 @@
+use crate::minifier::{Syntax, Whitespace};
+use proc_macro2::Span;
+use serde_derive::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use syn::{LitInt, LitStr};
+
+const CONFIG_FILE_NAME: &str = "askama.toml";
+const DEFAULT_SYNTAX_NAME: &str = "default";
+
+/// The parts of Askama's configuration file that affect how templates are
+/// found and scanned.
+pub(crate) struct Config {
+    pub(crate) root: PathBuf,
+    pub(crate) dirs: Vec<PathBuf>,
+    syntaxes: Vec<(String, Syntax)>,
+    default_syntax: String,
+    whitespace: Option<String>,
+    max_size: Option<usize>,
+    /// Custom elements treated as block-level, or built-in block-level
+    /// elements treated as inline, by `remove_block_whitespace`.
+    pub(crate) block_elements: Vec<String>,
+    pub(crate) inline_elements: Vec<String>,
+    /// The configuration file that was read, if any.
+    pub(crate) file: Option<PathBuf>,
+}
+
+#[derive(Default, Deserialize)]
+struct RawConfig {
+    general: Option<RawGeneral>,
+    syntax: Option<Vec<RawSyntax>>,
+    /// Settings of `askama-minify` itself, which Askama ignores.
+    #[serde(rename = "askama-minify")]
+    minify: Option<RawMinify>,
+}
+
+#[derive(Deserialize)]
+struct RawMinify {
+    max_size: Option<usize>,
+    block_elements: Option<Vec<String>>,
+    inline_elements: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct RawGeneral {
+    dirs: Option<Vec<String>>,
+    default_syntax: Option<String>,
+    whitespace: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawSyntax {
+    name: String,
+    block_start: Option<String>,
+    block_end: Option<String>,
+    expr_start: Option<String>,
+    expr_end: Option<String>,
+    comment_start: Option<String>,
+    comment_end: Option<String>,
+}
+
+impl RawSyntax {
+    fn into_syntax(self) -> (String, Syntax) {
+        let default = Syntax::default();
+        let syntax = Syntax {
+            block_start: self.block_start.unwrap_or(default.block_start),
+            block_end: self.block_end.unwrap_or(default.block_end),
+            expr_start: self.expr_start.unwrap_or(default.expr_start),
+            expr_end: self.expr_end.unwrap_or(default.expr_end),
+            comment_start: self.comment_start.unwrap_or(default.comment_start),
+            comment_end: self.comment_end.unwrap_or(default.comment_end),
+        };
+
+        (self.name, syntax)
+    }
+}
+
+impl Config {
+    /// Reads `askama.toml`, or the file named by the forwarded `config`
+    /// argument, from `CARGO_MANIFEST_DIR` the same way Askama does.
+    pub(crate) fn load(config_path: Option<&LitStr>) -> syn::Result<Self> {
+        let span = config_path.map_or_else(Span::call_site, LitStr::span);
+        let root = std::env::var_os("CARGO_MANIFEST_DIR")
+            .map(PathBuf::from)
+            .ok_or_else(|| syn::Error::new(span, "CARGO_MANIFEST_DIR is not set"))?;
+        let file =
+            root.join(config_path.map_or_else(|| CONFIG_FILE_NAME.to_owned(), LitStr::value));
+
+        let exists = file.is_file();
+        let raw = if exists {
+            let content = fs::read_to_string(&file).map_err(|error| {
+                syn::Error::new(
+                    span,
+                    format!("failed to read `{}`: {error}", file.display()),
+                )
+            })?;
+            basic_toml::from_str(&content).map_err(|error| {
+                syn::Error::new(
+                    span,
+                    format!("invalid TOML in `{}`: {error}", file.display()),
+                )
+            })?
+        } else if config_path.is_some() {
+            return Err(syn::Error::new(
+                span,
+                format!("config file `{}` does not exist", file.display()),
+            ));
+        } else {
+            RawConfig::default()
+        };
+
+        Ok(Self::from_raw(root, raw, exists.then_some(file)))
+    }
+
+    fn from_raw(root: PathBuf, raw: RawConfig, file: Option<PathBuf>) -> Self {
+        let (dirs, default_syntax, whitespace) = match raw.general {
+            Some(general) => (general.dirs, general.default_syntax, general.whitespace),
+            None => (None, None, None),
+        };
+        let dirs = dirs
+            .map(|dirs| dirs.iter().map(|dir| root.join(dir)).collect())
+            .unwrap_or_else(|| vec![root.join("templates")]);
+        let syntaxes = raw
+            .syntax
+            .unwrap_or_default()
+            .into_iter()
+            .map(RawSyntax::into_syntax)
+            .collect();
+
+        let (max_size, block_elements, inline_elements) = match raw.minify {
+            Some(minify) => (
+                minify.max_size,
+                minify.block_elements,
+                minify.inline_elements,
+            ),
+            None => (None, None, None),
+        };
+
+        Self {
+            root,
+            dirs,
+            syntaxes,
+            default_syntax: default_syntax.unwrap_or_else(|| DEFAULT_SYNTAX_NAME.to_owned()),
+            whitespace,
+            max_size,
+            block_elements: block_elements.unwrap_or_default(),
+            inline_elements: inline_elements.unwrap_or_default(),
+            file,
+        }
+    }
+
+    /// Returns the delimiters of the syntax selected with the forwarded
+    /// `syntax` argument, or of the configured default syntax.
+    pub(crate) fn syntax(&self, name: Option<&LitStr>) -> syn::Result<Syntax> {
+        let wanted = name.map_or_else(|| self.default_syntax.clone(), LitStr::value);
+        if let Some((_, syntax)) = self.syntaxes.iter().find(|(name, _)| *name == wanted) {
+            return Ok(syntax.clone());
+        }
+
+        if wanted == DEFAULT_SYNTAX_NAME {
+            return Ok(Syntax::default());
+        }
+
+        Err(syn::Error::new(
+            name.map_or_else(Span::call_site, LitStr::span),
+            format!("syntax `{wanted}` is not defined in the Askama config"),
+        ))
+    }
+
+    /// Returns the size budget of a minified template in bytes: the
+    /// `max_size` argument, or `max_size` in the `[askama-minify]` table.
+    pub(crate) fn max_size(&self, value: Option<&LitInt>) -> syn::Result<Option<usize>> {
+        match value {
+            Some(value) => value.base10_parse().map(Some),
+            None => Ok(self.max_size),
+        }
+    }
+
+    /// Returns the whitespace handling for tags without a control marker:
+    /// the forwarded `whitespace` argument, or the configured default.
+    pub(crate) fn whitespace(&self, value: Option<&LitStr>) -> syn::Result<Whitespace> {
+        let span = value.map_or_else(Span::call_site, LitStr::span);
+        let Some(value) = value.map(LitStr::value).or_else(|| self.whitespace.clone()) else {
+            return Ok(Whitespace::default());
+        };
+
+        Whitespace::parse(&value).ok_or_else(|| {
+            syn::Error::new(
+                span,
+                format!(
+                    "invalid value for `whitespace`: `{value}`; expected `preserve`, `suppress` or `minimize`"
+                ),
+            )
+        })
+    }
+
+    /// Resolves a template path against `CARGO_MANIFEST_DIR` first and then
+    /// each configured template directory in order. Askama itself only
+    /// searches the directories, so the crate root lookup is named in the
+    /// error.
+    pub(crate) fn resolve_template_path(&self, path: &str) -> Result<PathBuf, String> {
+        let raw = Path::new(path);
+        if raw.is_absolute() && raw.is_file() {
+            return Ok(raw.to_path_buf());
+        }
+
+        let candidates = std::iter::once(self.root.join(raw))
+            .chain(self.dirs.iter().map(|dir| dir.join(raw)))
+            .collect::<Vec<_>>();
+
+        candidates
+            .iter()
+            .find(|candidate| candidate.is_file())
+            .cloned()
+            .ok_or_else(|| {
+                let tried = candidates
+                    .iter()
+                    .enumerate()
+                    .map(|(index, candidate)| match index {
+                        0 => format!("`{}` (crate root)", candidate.display()),
+                        _ => format!("`{}`", candidate.display()),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("template `{path}` was not found; tried {tried}")
+            })
+    }
+}
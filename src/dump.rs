@@ -0,0 +1,79 @@
+use crate::output::{TemplateKey, crate_dir, write_file};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::ErrorKind;
+
+/// Directory that receives a copy of every minified template when set.
+pub(crate) const DUMP_DIR_VAR: &str = "ASKAMA_MINIFY_DUMP_DIR";
+
+const MANIFEST_FILE: &str = "manifest.toml";
+
+/// Held while the manifest is updated.
+const MANIFEST_LOCK: &str = "manifest.lock";
+
+/// A minified template as listed in the dump manifest.
+#[derive(Serialize, Deserialize)]
+struct ManifestEntry {
+    source: String,
+    file: String,
+    original_len: usize,
+    minified_len: usize,
+}
+
+pub(crate) struct DumpedTemplate<'a> {
+    pub(crate) key: &'a TemplateKey<'a>,
+    /// The `path` of the template, or how inline templates are shown.
+    pub(crate) source: &'a str,
+    pub(crate) ext: &'a str,
+    pub(crate) original_len: usize,
+    pub(crate) minified: &'a str,
+}
+
+/// Writes a minified template into the dump directory when
+/// `ASKAMA_MINIFY_DUMP_DIR` is set, and records it in the `manifest.toml`
+/// next to it. Several compilers can expand the templates of a crate at
+/// once, such as for a library and its tests or in an editor, so the
+/// manifest is only updated under a file lock.
+pub(crate) fn dump_template(template: &DumpedTemplate<'_>) -> Result<(), String> {
+    let Some(dir) = crate_dir(DUMP_DIR_VAR) else {
+        return Ok(());
+    };
+    let file = template.key.file(template.ext);
+    write_file(&dir.join(&file), template.minified)?;
+
+    let lock_path = dir.join(MANIFEST_LOCK);
+    let lock = File::create(&lock_path)
+        .and_then(|lock| lock.lock().map(|()| lock))
+        .map_err(|error| format!("failed to lock `{}`: {error}", lock_path.display()))?;
+    let manifest_path = dir.join(MANIFEST_FILE);
+    let mut manifest: BTreeMap<String, ManifestEntry> = match fs::read_to_string(&manifest_path) {
+        Ok(content) => basic_toml::from_str(&content).map_err(|error| {
+            format!(
+                "failed to read the dump manifest `{}`, delete it to start over: {error}",
+                manifest_path.display()
+            )
+        })?,
+        Err(error) if error.kind() == ErrorKind::NotFound => BTreeMap::new(),
+        Err(error) => {
+            return Err(format!(
+                "failed to read `{}`: {error}",
+                manifest_path.display()
+            ));
+        }
+    };
+    manifest.insert(
+        template.key.id(),
+        ManifestEntry {
+            source: template.source.to_owned(),
+            file,
+            original_len: template.original_len,
+            minified_len: template.minified.len(),
+        },
+    );
+    let content = basic_toml::to_string(&manifest)
+        .map_err(|error| format!("failed to write the dump manifest: {error}"))?;
+    write_file(&manifest_path, &content)?;
+    drop(lock);
+    Ok(())
+}
@@ -1,38 +1,608 @@
-use crate::args::MacroArgs;
-use crate::item::reject_existing_template_attr;
-use crate::loader::{load_template, minify_template_source};
-use proc_macro2::{Span, TokenStream as TokenStream2};
-use quote::quote;
-use syn::{Attribute, DeriveInput, LitStr, parse_quote};
+use crate::args::{CFG_ACTIVE, CFG_INACTIVE, DebugOutput, MacroArgs, TemplateInput};
+use crate::dump::{DUMP_DIR_VAR, DumpedTemplate, dump_template};
+use crate::item::{derives_template, reject_existing_template_attr, template_from_docs};
+use crate::loader::{INLINE_ORIGIN, load_template, minify_template_source};
+use crate::minifier::{Stats, Syntax, inline_elements};
+use crate::output::TemplateKey;
+use crate::report::{REPORT_DIR_VAR, ReportedTemplate, report_template};
+use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
+use quote::{ToTokens, quote, quote_spanned};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use syn::parse::Parser;
+use syn::punctuated::Punctuated;
+use syn::{Attribute, Data, DeriveInput, LitBool, LitStr, Meta, Token, parse_quote};
 
+const ATTRIBUTE_NAME: &str = "template_minify";
+
+/// Expands `template_minify` with the arguments `args`, parsed from
+/// `tokens`, on `item`.
 pub(crate) fn expand_template_minify(
+    tokens: TokenStream2,
     args: MacroArgs,
     mut item: DeriveInput,
 ) -> syn::Result<TokenStream2> {
-    reject_existing_template_attr(&item)?;
+    // Without its own template, `template_minify` rewrites the existing
+    // `#[template(...)]` attributes in place.
+    let rewrite = args.input.is_none() && args.in_doc.is_none();
+    if !rewrite {
+        reject_existing_template_attr(&item.attrs)?;
+    } else if let Some(predicate) = unevaluated_cfg(&item, &args)? {
+        return branch_on_cfg(tokens, &predicate, item);
+    }
 
-    let template = load_template(&args)?;
-    let source = LitStr::new(
-        &minify_template_source(&template.source, &template.ext),
-        Span::call_site(),
-    );
-    let ext = LitStr::new(&template.ext, Span::call_site());
-    let passthrough = args.passthrough;
+    let mut emitted = Emitted::default();
+    let name = item.ident.to_string();
+    let mut rewriter = Rewriter::new(&name, &args, &args.passthrough, &mut emitted);
+    if rewrite {
+        rewriter.rewrite_attrs(&mut item.attrs)?;
+    }
+    let item_rewritten = rewriter.rewritten;
+    let partial = rewriter.partial.take();
+    let mut has_templates = item_rewritten;
+    let variant_defaults = partial.as_ref().unwrap_or(&args);
+
+    if let Data::Enum(data) = &mut item.data {
+        for variant in &mut data.variants {
+            let variant_name = format!("{name}::{}", variant.ident);
+            let Some(variant_attr) = take_variant_attr(&mut variant.attrs)? else {
+                if rewrite {
+                    let mut rewriter =
+                        Rewriter::new(&variant_name, variant_defaults, &[], &mut emitted);
+                    rewriter.rewrite_attrs(&mut variant.attrs)?;
+                    has_templates |= rewriter.rewritten;
+                }
+                continue;
+            };
+
+            reject_existing_template_attr(&variant.attrs)?;
+            let variant_args =
+                MacroArgs::parse_nested(variant_attr.meta.require_list()?, variant_defaults)?;
+            if let Some(metadata) = &variant_args.metadata {
+                return Err(syn::Error::new_spanned(
+                    metadata,
+                    "`metadata` can only be used on the item, not on a variant",
+                ));
+            }
+            let Some(input) = &template_input(&variant_args, &variant.attrs)? else {
+                return Err(syn::Error::new_spanned(
+                    variant_attr,
+                    "expected `path = \"...\"`, `source = \"...\"`, or `in_doc = true`",
+                ));
+            };
+
+            let meta = minified_template_meta(&variant_name, input, &variant_args, &mut emitted)?;
+            variant.attrs.push(parse_quote!(#[#meta]));
+            has_templates = true;
+        }
+    }
 
-    let template_attr: Attribute = parse_quote! {
-        #[template(source = #source, ext = #ext #(, #passthrough)*)]
+    match &template_input(&args, &item.attrs)? {
+        Some(input) => {
+            let meta = minified_template_meta(&name, input, &args, &mut emitted)?;
+            item.attrs.push(parse_quote!(#[#meta]));
+        }
+        // The existing attributes were rewritten in place, or an enum-level
+        // `#[template(...)]` already holds the arguments for the variants.
+        None if item_rewritten || (has_templates && partial.is_some()) => {}
+        // Askama passes the enum's arguments down to every variant.
+        None if has_templates => {
+            let ext = args.ext.iter();
+            let passthrough = &args.passthrough;
+            if args.ext.is_some() || !passthrough.is_empty() {
+                item.attrs.push(parse_quote! {
+                    #[template(#(ext = #ext,)* #(#passthrough),*)]
+                });
+            }
+        }
+        // The compiler drops `cfg_attr` attributes whose predicate does not
+        // hold before expanding the item, which can leave it with neither a
+        // template nor `derive(Template)`, and so can the branch of a
+        // predicate on its variants that does not hold. It is kept as
+        // written then.
+        None if !derives_template(&item.attrs) || args.cfgs.iter().any(|&(_, holds)| !holds) => {
+            return Ok(item.into_token_stream());
+        }
+        None => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "expected `path = \"...\"`, `source = \"...\"`, `in_doc = true`, or an existing `#[template(...)]` attribute",
+            ));
+        }
+    }
+
+    let metadata = match &args.metadata {
+        Some(flag) => Some(metadata_impl(&item, flag, &emitted.templates)?),
+        None => None,
     };
-    item.attrs.push(template_attr);
 
-    let tracking = template.include_path.map(|path| {
+    let tracking = emitted.tracked.iter().map(|path| {
         let path = LitStr::new(&path.to_string_lossy(), Span::call_site());
         quote! {
             const _: &str = include_str!(#path);
         }
     });
+    let diagnostics = &emitted.diagnostics;
 
+    // Reading the variables with `option_env!` makes Cargo expand the
+    // templates again when they change.
     Ok(quote! {
         #item
-        #tracking
+        #metadata
+        #(#tracking)*
+        #(#diagnostics)*
+        const _: ::core::option::Option<&str> = ::core::option_env!(#DUMP_DIR_VAR);
+        const _: ::core::option::Option<&str> = ::core::option_env!(#REPORT_DIR_VAR);
+    })
+}
+
+/// Returns the predicate of the first `#[cfg_attr(...)]` around a
+/// `#[template(...)]` on the item or its variants that no outer expansion
+/// has branched on yet.
+fn unevaluated_cfg(item: &DeriveInput, args: &MacroArgs) -> syn::Result<Option<Meta>> {
+    let variants = match &item.data {
+        Data::Enum(data) => data.variants.iter().collect(),
+        _ => Vec::new(),
+    };
+    let attrs = item
+        .attrs
+        .iter()
+        .chain(variants.iter().flat_map(|variant| &variant.attrs));
+    for attr in attrs {
+        if let Some(predicate) = unevaluated_cfg_in(&attr.meta, args, false)? {
+            return Ok(Some(predicate));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Finds an unevaluated predicate around a template in `meta`. Behind an
+/// unevaluated predicate, any template found returns `Some`.
+fn unevaluated_cfg_in(
+    meta: &Meta,
+    args: &MacroArgs,
+    behind_unevaluated: bool,
+) -> syn::Result<Option<Meta>> {
+    let Meta::List(list) = meta else {
+        return Ok(None);
+    };
+    if list.path.is_ident("template") {
+        return Ok(behind_unevaluated.then(|| meta.clone()));
+    }
+    if !list.path.is_ident("cfg_attr") {
+        return Ok(None);
+    }
+
+    let metas = list.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
+    let Some(predicate) = metas.first() else {
+        return Ok(None);
+    };
+    let holds = args.cfg_holds(predicate);
+    for meta in metas.iter().skip(1) {
+        match holds {
+            Some(false) => {}
+            Some(true) => {
+                if let Some(found) = unevaluated_cfg_in(meta, args, false)? {
+                    return Ok(Some(found));
+                }
+            }
+            None => {
+                if unevaluated_cfg_in(meta, args, true)?.is_some() {
+                    return Ok(Some(predicate.clone()));
+                }
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Leaves `item` to two nested `template_minify` expansions, one for each
+/// outcome of `predicate`, so templates behind a `cfg_attr` that does not
+/// hold are never loaded or reported.
+fn branch_on_cfg(
+    tokens: TokenStream2,
+    predicate: &Meta,
+    mut item: DeriveInput,
+) -> syn::Result<TokenStream2> {
+    let metas = Punctuated::<Meta, Token![,]>::parse_terminated.parse2(tokens)?;
+    let branch = |state: &str| {
+        let state = Ident::new(state, Span::call_site());
+        let mut metas = metas.clone();
+        metas.push(parse_quote!(#state(#predicate)));
+        metas
+    };
+    let active = branch(CFG_ACTIVE);
+    let inactive = branch(CFG_INACTIVE);
+    item.attrs.splice(
+        0..0,
+        [
+            parse_quote!(#[cfg_attr(#predicate, ::askama_minify::template_minify(#active))]),
+            parse_quote!(#[cfg_attr(not(#predicate), ::askama_minify::template_minify(#inactive))]),
+        ],
+    );
+
+    Ok(item.into_token_stream())
+}
+
+/// Items generated next to the template item.
+#[derive(Default)]
+struct Emitted {
+    /// Files to track with `include_str!` so Cargo rebuilds on changes.
+    tracked: Vec<PathBuf>,
+    /// Warnings and `debug` notes, shown through deprecated items.
+    diagnostics: Vec<TokenStream2>,
+    templates: Vec<MinifiedTemplate>,
+}
+
+/// A template minified for the item or one of its variants.
+struct MinifiedTemplate {
+    /// The item, with `::Variant` for enum variants.
+    name: String,
+    path: Option<String>,
+    original_len: usize,
+    minified: String,
+}
+
+/// Builds the associated constants of `metadata = true` from the template
+/// of the item itself.
+fn metadata_impl(
+    item: &DeriveInput,
+    flag: &LitBool,
+    templates: &[MinifiedTemplate],
+) -> syn::Result<TokenStream2> {
+    let name = item.ident.to_string();
+    let mut own = templates.iter().filter(|template| template.name == name);
+    let (Some(template), None) = (own.next(), own.next()) else {
+        return Err(syn::Error::new_spanned(
+            flag,
+            "`metadata = true` needs exactly one template on the item itself",
+        ));
+    };
+
+    let ident = &item.ident;
+    let vis = &item.vis;
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+    let minified = LitStr::new(&template.minified, Span::call_site());
+    let original_len = template.original_len;
+    let minified_len = template.minified.len();
+    let source_path = match &template.path {
+        Some(path) => quote!(::core::option::Option::Some(#path)),
+        None => quote!(::core::option::Option::None),
+    };
+
+    Ok(quote! {
+        impl #impl_generics #ident #ty_generics #where_clause {
+            /// The minified template source given to Askama.
+            #vis const MINIFIED_SOURCE: &'static str = #minified;
+            /// Size of the template in bytes before minification.
+            #vis const ORIGINAL_LEN: usize = #original_len;
+            /// Size of the template in bytes after minification.
+            #vis const MINIFIED_LEN: usize = #minified_len;
+            /// The `path` of the template, or `None` for inline templates.
+            #vis const SOURCE_PATH: ::core::option::Option<&'static str> = #source_path;
+        }
     })
 }
+
+/// Builds an item whose use of a deprecated struct called `name` makes
+/// rustc print `message` as a warning at `span`.
+fn deprecated_item(span: Span, name: &str, message: &str) -> TokenStream2 {
+    let name = Ident::new(name, span);
+    let note = format!("askama-minify: {message}");
+    quote_spanned! {span=>
+        const _: () = {
+            #[deprecated(note = #note)]
+            struct #name;
+            _ = #name;
+        };
+    }
+}
+
+/// Number of inline elements listed when a template is over its budget.
+const LARGEST_ELEMENTS: usize = 3;
+
+/// Explains by how much a minified template exceeds `max_size`, with its
+/// largest inline `<script>` and `<style>` elements.
+fn size_budget_error(max_size: usize, original: &str, minified: &str, syntax: &Syntax) -> String {
+    let mut message = format!(
+        "minified template is {} bytes, over the `max_size` of {max_size} bytes (original: {} bytes)",
+        minified.len(),
+        original.len()
+    );
+
+    let mut elements = inline_elements(minified, syntax);
+    elements.retain(|element| !element.content.is_empty());
+    elements.sort_by_key(|element| std::cmp::Reverse(element.content.len()));
+    if !elements.is_empty() {
+        message.push_str("\nlargest inline elements:");
+    }
+    for element in elements.iter().take(LARGEST_ELEMENTS) {
+        let preview: String = element.content.chars().take(32).collect();
+        let ellipsis = if preview.len() < element.content.len() {
+            "..."
+        } else {
+            ""
+        };
+        message.push_str(&format!(
+            "\n  `<{}>` with {} bytes: `{preview}{ellipsis}`",
+            element.name,
+            element.content.len()
+        ));
+    }
+
+    message
+}
+
+/// Describes the minification of a template for `debug = "..."`.
+fn debug_note(
+    input: &TemplateInput,
+    output: DebugOutput,
+    original: &str,
+    minified: &str,
+) -> String {
+    let name = match input {
+        TemplateInput::Path(path) => format!("`{}`", path.value()),
+        TemplateInput::Source(_) | TemplateInput::Doc(_) => "inline template".to_owned(),
+    };
+    let saved = original.len().saturating_sub(minified.len());
+    let percent = if original.is_empty() {
+        0.0
+    } else {
+        saved as f64 * 100.0 / original.len() as f64
+    };
+    let summary = format!(
+        "{name} minified from {} to {} bytes, saving {saved} bytes ({percent:.1}%)",
+        original.len(),
+        minified.len()
+    );
+
+    match output {
+        DebugOutput::Minified => format!("{summary}\n{minified}"),
+        DebugOutput::Diff => {
+            format!("{summary}\n--- original\n{original}\n+++ minified\n{minified}")
+        }
+    }
+}
+
+/// Returns the template given by `path` or `source`, or read from the
+/// documentation in `attrs` for `in_doc = true`.
+fn template_input(args: &MacroArgs, attrs: &[Attribute]) -> syn::Result<Option<TemplateInput>> {
+    match &args.in_doc {
+        Some(in_doc) => {
+            template_from_docs(attrs, in_doc).map(|source| Some(TemplateInput::Doc(source)))
+        }
+        None => Ok(args.input.clone()),
+    }
+}
+
+/// Loads and minifies a template and builds the `template(...)` meta that
+/// replaces it. Files to track and warnings are added to `emitted`, and the
+/// result is dumped and reported for the item or variant `name` when
+/// requested.
+fn minified_template_meta(
+    name: &str,
+    input: &TemplateInput,
+    args: &MacroArgs,
+    emitted: &mut Emitted,
+) -> syn::Result<Meta> {
+    let template = load_template(input, args)?;
+    let mut warnings = Vec::new();
+    let mut stats = Stats::default();
+    let minified = minify_template_source(
+        &template.source,
+        &template.ext,
+        &template.options,
+        &mut warnings,
+        &mut stats,
+    );
+    let source = LitStr::new(&minified, Span::call_site());
+    let ext = LitStr::new(&template.ext, Span::call_site());
+    let passthrough = &args.passthrough;
+
+    // The same warning can come up for several `<style>` or `<script>`
+    // elements; it is reported once.
+    let mut seen = HashSet::new();
+    warnings.retain(|warning| seen.insert(warning.clone()));
+    let literal = input.literal();
+    if args.deny_warnings && !warnings.is_empty() {
+        return Err(syn::Error::new_spanned(literal, warnings.join("\n")));
+    }
+    emitted.diagnostics.extend(
+        warnings
+            .iter()
+            .map(|message| deprecated_item(literal.span(), "TemplateMinifyWarning", message)),
+    );
+    if let Some(max_size) = template
+        .max_size
+        .filter(|&max_size| minified.len() > max_size)
+    {
+        return Err(syn::Error::new_spanned(
+            literal,
+            size_budget_error(
+                max_size,
+                &template.source,
+                &minified,
+                &template.options.syntax,
+            ),
+        ));
+    }
+
+    let path = match input {
+        TemplateInput::Path(path) => Some(path.value()),
+        TemplateInput::Source(_) | TemplateInput::Doc(_) => None,
+    };
+    // Later templates of the same item, such as under `cfg_attr`, are
+    // numbered so that they are dumped and reported separately.
+    let index = emitted
+        .templates
+        .iter()
+        .filter(|template| template.name == name)
+        .count();
+    let numbered = match index {
+        0 => name.to_owned(),
+        index => format!("{name}#{}", index + 1),
+    };
+    let rust_file = literal.span().unwrap().file();
+    let key = TemplateKey {
+        rust_file: &rust_file,
+        name: &numbered,
+    };
+    dump_template(&DumpedTemplate {
+        key: &key,
+        source: path.as_deref().unwrap_or(INLINE_ORIGIN),
+        ext: &template.ext,
+        original_len: template.source.len(),
+        minified: &minified,
+    })
+    .and_then(|()| {
+        report_template(&ReportedTemplate {
+            key: &key,
+            source: path.as_deref().unwrap_or(INLINE_ORIGIN),
+            ext: &template.ext,
+            original_len: template.source.len(),
+            minified_len: minified.len(),
+            stats: &stats,
+        })
+    })
+    .map_err(|message| syn::Error::new_spanned(literal, message))?;
+
+    if let Some(output) = args.debug {
+        let note = debug_note(input, output, &template.source, &minified);
+        emitted
+            .diagnostics
+            .push(deprecated_item(literal.span(), "TemplateMinifyNote", &note));
+    }
+
+    emitted.templates.push(MinifiedTemplate {
+        name: name.to_owned(),
+        path,
+        original_len: template.source.len(),
+        minified,
+    });
+
+    for path in template.include_paths {
+        if !emitted.tracked.contains(&path) {
+            emitted.tracked.push(path);
+        }
+    }
+
+    Ok(parse_quote! {
+        template(source = #source, ext = #ext #(, #passthrough)*)
+    })
+}
+
+/// Minifies existing `#[template(...)]` attributes in place, including the
+/// ones nested in `#[cfg_attr(...)]`. All other arguments are kept.
+struct Rewriter<'a> {
+    /// The item or variant the attributes belong to.
+    name: &'a str,
+    /// Arguments of `template_minify`, the defaults for every template.
+    defaults: &'a MacroArgs,
+    /// Forwarded `template_minify` arguments appended to each template.
+    extra: &'a [Meta],
+    emitted: &'a mut Emitted,
+    rewritten: bool,
+    /// The first `#[template(...)]` without a template, such as the shared
+    /// arguments of an enum, parsed as defaults for its variants.
+    partial: Option<MacroArgs>,
+}
+
+impl<'a> Rewriter<'a> {
+    fn new(
+        name: &'a str,
+        defaults: &'a MacroArgs,
+        extra: &'a [Meta],
+        emitted: &'a mut Emitted,
+    ) -> Self {
+        Self {
+            name,
+            defaults,
+            extra,
+            emitted,
+            rewritten: false,
+            partial: None,
+        }
+    }
+
+    fn rewrite_attrs(&mut self, attrs: &mut [Attribute]) -> syn::Result<()> {
+        // `in_doc` templates read the docs, which the loop below borrows.
+        let docs = attrs.to_vec();
+        for attr in attrs {
+            self.rewrite_meta(&mut attr.meta, &docs)?;
+        }
+
+        Ok(())
+    }
+
+    fn rewrite_meta(&mut self, meta: &mut Meta, docs: &[Attribute]) -> syn::Result<()> {
+        let Meta::List(list) = meta else {
+            return Ok(());
+        };
+
+        if list.path.is_ident("cfg_attr") {
+            let mut metas =
+                list.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
+            // The first meta is the `cfg` predicate. Templates behind one
+            // that does not hold are left alone.
+            if metas
+                .first()
+                .is_some_and(|predicate| self.defaults.cfg_holds(predicate) == Some(false))
+            {
+                return Ok(());
+            }
+            for meta in metas.iter_mut().skip(1) {
+                self.rewrite_meta(meta, docs)?;
+            }
+            list.tokens = metas.to_token_stream();
+            return Ok(());
+        }
+
+        if !list.path.is_ident("template") {
+            return Ok(());
+        }
+
+        let mut args = MacroArgs::parse_nested(list, self.defaults)?;
+        let Some(input) = template_input(&args, docs)? else {
+            if !self.extra.is_empty() {
+                let mut metas =
+                    list.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
+                metas.extend(self.extra.iter().cloned());
+                list.tokens = metas.to_token_stream();
+            }
+            if self.partial.is_none() {
+                self.partial = Some(args);
+            }
+            return Ok(());
+        };
+
+        args.passthrough.extend(self.extra.iter().cloned());
+        *meta = minified_template_meta(self.name, &input, &args, self.emitted)?;
+        self.rewritten = true;
+        Ok(())
+    }
+}
+
+/// Removes the `#[template_minify(...)]` attribute of an enum variant.
+fn take_variant_attr(attrs: &mut Vec<Attribute>) -> syn::Result<Option<Attribute>> {
+    let mut found = None;
+    let mut index = 0;
+    while index < attrs.len() {
+        if !attrs[index].path().is_ident(ATTRIBUTE_NAME) {
+            index += 1;
+            continue;
+        }
+
+        let attr = attrs.remove(index);
+        if found.is_some() {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "duplicate `template_minify` attribute",
+            ));
+        }
+        found = Some(attr);
+    }
+
+    Ok(found)
+}
@@ -1,5 +1,6 @@
-use syn::DeriveInput;
 use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{Attribute, DeriveInput, Expr, ExprLit, Lit, LitBool, LitStr, Meta, Path, Token};
 
 pub(crate) struct TemplateItem(pub(crate) DeriveInput);
 
@@ -14,8 +15,8 @@ impl Parse for TemplateItem {
     }
 }
 
-pub(crate) fn reject_existing_template_attr(item: &DeriveInput) -> syn::Result<()> {
-    for attr in &item.attrs {
+pub(crate) fn reject_existing_template_attr(attrs: &[Attribute]) -> syn::Result<()> {
+    for attr in attrs {
         if attr.path().is_ident("template") {
             return Err(syn::Error::new_spanned(
                 attr,
@@ -26,3 +27,149 @@ pub(crate) fn reject_existing_template_attr(item: &DeriveInput) -> syn::Result<(
 
     Ok(())
 }
+
+/// Whether `attrs` derive `Template`, by any path.
+pub(crate) fn derives_template(attrs: &[Attribute]) -> bool {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("derive"))
+        .any(|attr| {
+            attr.parse_args_with(Punctuated::<Path, Token![,]>::parse_terminated)
+                .is_ok_and(|paths| {
+                    paths.iter().any(|path| {
+                        path.segments
+                            .last()
+                            .is_some_and(|segment| segment.ident == "Template")
+                    })
+                })
+        })
+}
+
+/// Code block languages Askama reads `in_doc` templates from.
+const TEMPLATE_LANGUAGES: &[&str] = &["askama", "j2", "jinja", "jinja2", "rinja"];
+
+/// Reads the template of an `in_doc = true` item the way Askama does: the
+/// `///` comments are joined, their common indentation is removed, and
+/// every fenced `askama` code block is collected. The returned literal is
+/// spanned on the first doc comment.
+pub(crate) fn template_from_docs(attrs: &[Attribute], in_doc: &LitBool) -> syn::Result<LitStr> {
+    let mut span = None;
+    let mut docs = String::new();
+    for attr in attrs {
+        let Meta::NameValue(meta) = &attr.meta else {
+            continue;
+        };
+        if !meta.path.is_ident("doc") {
+            continue;
+        }
+
+        let mut value = &meta.value;
+        while let Expr::Group(group) = value {
+            value = &group.expr;
+        }
+        let Expr::Lit(ExprLit {
+            lit: Lit::Str(doc), ..
+        }) = value
+        else {
+            continue;
+        };
+
+        span.get_or_insert_with(|| doc.span());
+        docs.push_str(&doc.value());
+        docs.push('\n');
+    }
+
+    span.zip(fenced_template(&strip_common_indent(&docs)))
+        .map(|(span, template)| LitStr::new(&template, span))
+        .ok_or_else(|| {
+            syn::Error::new_spanned(
+                in_doc,
+                "`in_doc = true` needs an `askama` code block in the documentation",
+            )
+        })
+}
+
+fn strip_common_indent(docs: &str) -> String {
+    let mut indents = docs
+        .lines()
+        .filter_map(|line| Some(&line[..line.find(|ch: char| !ch.is_ascii_whitespace())?]));
+    let mut common = indents.next().unwrap_or_default();
+    for indent in indents {
+        let shared = common
+            .char_indices()
+            .zip(indent.chars())
+            .find(|((_, left), right)| left != right)
+            .map_or(common.len().min(indent.len()), |((index, _), _)| index);
+        common = &common[..shared];
+    }
+
+    docs.lines()
+        .flat_map(|line| [line.get(common.len()..).unwrap_or_default(), "\n"])
+        .collect()
+}
+
+/// Joins the contents of every fenced code block tagged with one of the
+/// template languages, or returns `None` when there is none.
+fn fenced_template(docs: &str) -> Option<String> {
+    struct Fence {
+        marker: char,
+        len: usize,
+        indent: usize,
+        template: bool,
+    }
+
+    let mut template = String::new();
+    let mut found = false;
+    let mut fence: Option<Fence> = None;
+
+    for line in docs.lines() {
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        let trimmed = &line[indent..];
+        let marker_len = |marker: char| trimmed.len() - trimmed.trim_start_matches(marker).len();
+
+        match &fence {
+            Some(open) => {
+                let closes = indent <= 3
+                    && marker_len(open.marker) >= open.len
+                    && trimmed
+                        .trim_start_matches(open.marker)
+                        .trim_ascii()
+                        .is_empty();
+                if closes {
+                    fence = None;
+                } else if open.template {
+                    let strip = indent.min(open.indent);
+                    template.push_str(&line[strip..]);
+                    template.push('\n');
+                }
+            }
+            None if indent <= 3 => {
+                let Some(marker) = trimmed.chars().next().filter(|ch| matches!(ch, '`' | '~'))
+                else {
+                    continue;
+                };
+                let len = marker_len(marker);
+                if len < 3 {
+                    continue;
+                }
+
+                let is_template = trimmed[len..]
+                    .split(',')
+                    .any(|language| TEMPLATE_LANGUAGES.contains(&language.trim_ascii()));
+                found |= is_template;
+                fence = Some(Fence {
+                    marker,
+                    len,
+                    indent,
+                    template: is_template,
+                });
+            }
+            None => {}
+        }
+    }
+
+    if template.ends_with('\n') {
+        template.pop();
+    }
+    found.then_some(template)
+}
@@ -2,10 +2,14 @@ use proc_macro::TokenStream;
 use syn::parse_macro_input;
 
 mod args;
+mod config;
+mod dump;
 mod expand;
 mod item;
 mod loader;
 mod minifier;
+mod output;
+mod report;
 
 use args::MacroArgs;
 use expand::expand_template_minify;
@@ -27,14 +31,24 @@ use item::TemplateItem;
 /// }
 /// ```
 ///
+/// On an enum, variants can carry their own `#[template_minify(...)]`, which
+/// is expanded into a minified `#[template(...)]` for that variant.
+///
+/// Without `path`, `source`, or `in_doc`, the existing `#[template(...)]`
+/// attributes of the item are minified in place instead, including those
+/// inside `#[cfg_attr(...)]`. Templates inside `#[cfg_attr(...)]` are only
+/// loaded when their predicate holds.
+///
 /// Template paths are resolved relative to `CARGO_MANIFEST_DIR`; if that file
-/// does not exist, `templates/<path>` is tried to match Askama's default layout.
+/// does not exist, each template directory configured in `askama.toml` is
+/// tried in order, which is `templates/<path>` in Askama's default layout.
 #[proc_macro_attribute]
 pub fn template_minify(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let tokens = attr.clone().into();
     let args = parse_macro_input!(attr with MacroArgs::parse);
     let item = parse_macro_input!(item as TemplateItem).0;
 
-    match expand_template_minify(args, item) {
+    match expand_template_minify(tokens, args, item) {
         Ok(tokens) => tokens.into(),
         Err(error) => error.to_compile_error().into(),
     }
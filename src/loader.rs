@@ -1,40 +1,148 @@
+mod extends;
+mod import;
+mod include;
+
 use crate::args::{MacroArgs, TemplateInput};
-use crate::minifier;
+use crate::config::Config;
+use crate::minifier::{self, Diagnostic, Options, Stats, Syntax};
+use extends::flatten_extends;
+use import::inline_imports;
+use include::inline_includes;
 use std::fs;
 use std::path::{Path, PathBuf};
 use syn::LitStr;
 
+/// How inline templates are named in diagnostics, as in Askama's errors.
+pub(crate) const INLINE_ORIGIN: &str = "<source attribute>";
+
 pub(crate) struct LoadedTemplate {
     pub(crate) source: String,
     pub(crate) ext: String,
-    pub(crate) include_path: Option<PathBuf>,
+    pub(crate) options: Options,
+    /// Size budget of the minified template in bytes.
+    pub(crate) max_size: Option<usize>,
+    pub(crate) include_paths: Vec<PathBuf>,
+}
+
+/// Resolves templates referenced while loading and records every file that
+/// needs `include_str!` tracking.
+struct Resolver<'a> {
+    config: &'a Config,
+    syntax: &'a Syntax,
+    tracked: Vec<PathBuf>,
+    /// Positions of the marked starts of HTML comments and elements, while
+    /// a failed check of a composed template is traced back to its files.
+    marks: Option<Vec<Mark>>,
 }
 
-pub(crate) fn load_template(args: &MacroArgs) -> syn::Result<LoadedTemplate> {
-    match &args.input {
-        TemplateInput::Source(source) => {
+impl Resolver<'_> {
+    /// Resolves a template referenced from another template. Like Askama, a
+    /// path next to the referencing file wins over the regular lookup.
+    fn resolve(&self, path: &str, origin: Option<&Path>) -> Result<PathBuf, String> {
+        if let Some(origin) = origin {
+            let sibling = origin.with_file_name(path);
+            if sibling.is_file() {
+                return Ok(sibling);
+            }
+        }
+
+        self.config.resolve_template_path(path)
+    }
+
+    /// Reads a template pulled in by another template. Askama drops the final
+    /// newline of template files, so the same is done before inlining.
+    ///
+    /// Only its Askama tags are checked here: HTML comments and elements may
+    /// be opened in one template and closed in another, so they are checked
+    /// once the template is composed.
+    fn read(&mut self, path: &Path) -> Result<String, String> {
+        let mut source = read_template(path)?;
+        let origin = path.display().to_string();
+        check_template(&source, &origin, false, self.syntax)?;
+        if source.ends_with('\n') {
+            source.pop();
+        }
+        if let Some(marks) = &mut self.marks {
+            source = mark_html_starts(&source, &origin, self.syntax, marks);
+        }
+
+        Ok(source)
+    }
+
+    fn track(&mut self, path: &Path) {
+        if !self.tracked.iter().any(|tracked| tracked == path) {
+            self.tracked.push(path.to_path_buf());
+        }
+    }
+}
+
+pub(crate) fn load_template(
+    input: &TemplateInput,
+    args: &MacroArgs,
+) -> syn::Result<LoadedTemplate> {
+    let config = Config::load(args.config.as_ref())?;
+    let options = Options {
+        syntax: config.syntax(args.syntax.as_ref())?,
+        whitespace: config.whitespace(args.whitespace.as_ref())?,
+        strip_block_whitespace: args.strip_block_whitespace,
+        remove_block_whitespace: args.remove_block_whitespace,
+        omit_optional_tags: args.omit_optional_tags,
+        remove_attribute_quotes: args.remove_attribute_quotes,
+        collapse_boolean_attributes: args.collapse_boolean_attributes,
+        remove_redundant_attributes: args.remove_redundant_attributes,
+        block_elements: config.block_elements.clone(),
+        inline_elements: config.inline_elements.clone(),
+        preserve_lines: args.preserve_lines,
+    };
+    let max_size = config.max_size(args.max_size.as_ref())?;
+    let mut resolver = Resolver {
+        config: &config,
+        syntax: &options.syntax,
+        tracked: Vec::new(),
+        marks: None,
+    };
+    if let Some(file) = &config.file {
+        resolver.track(file);
+    }
+
+    match input {
+        TemplateInput::Source(source) | TemplateInput::Doc(source) => {
             let Some(ext) = &args.ext else {
+                let kind = match input {
+                    TemplateInput::Doc(_) => "`in_doc`",
+                    _ => "`source`",
+                };
                 return Err(syn::Error::new_spanned(
                     source,
-                    "`source` templates require `ext = \"...\"`",
+                    format!("{kind} templates require `ext = \"...\"`"),
                 ));
             };
 
+            let value = source.value();
+            let source = load_source(
+                &value,
+                None,
+                INLINE_ORIGIN,
+                is_html(&ext.value()),
+                args,
+                &mut resolver,
+            )
+            .map_err(|message| syn::Error::new_spanned(source, message))?;
+
             Ok(LoadedTemplate {
-                source: source.value(),
+                source,
                 ext: ext.value(),
-                include_path: None,
+                include_paths: resolver.tracked,
+                options,
+                max_size,
             })
         }
         TemplateInput::Path(path) => {
-            let resolved = resolve_template_path(&path.value())
+            let resolved = config
+                .resolve_template_path(&path.value())
+                .map_err(|message| syn::Error::new_spanned(path, message))?;
+            let source = read_template(&resolved)
                 .map_err(|message| syn::Error::new_spanned(path, message))?;
-            let source = fs::read_to_string(&resolved).map_err(|error| {
-                syn::Error::new_spanned(
-                    path,
-                    format!("failed to read template `{}`: {error}", resolved.display()),
-                )
-            })?;
             let ext = args
                 .ext
                 .as_ref()
@@ -47,49 +155,219 @@ pub(crate) fn load_template(args: &MacroArgs) -> syn::Result<LoadedTemplate> {
                     )
                 })?;
 
+            resolver.track(&resolved);
+            let origin = resolved.display().to_string();
+            let source = load_source(
+                &source,
+                Some(&resolved),
+                &origin,
+                is_html(&ext),
+                args,
+                &mut resolver,
+            )
+            .map_err(|message| syn::Error::new_spanned(path, message))?;
+
             Ok(LoadedTemplate {
                 source,
                 ext,
-                include_path: Some(resolved),
+                include_paths: resolver.tracked,
+                options,
+                max_size,
             })
         }
     }
 }
 
-pub(crate) fn minify_template_source(source: &str, ext: &str) -> String {
-    if matches!(ext.to_ascii_lowercase().as_str(), "html" | "htm") {
-        minifier::minify_html(source)
+pub(crate) fn minify_template_source(
+    source: &str,
+    ext: &str,
+    options: &Options,
+    warnings: &mut Vec<String>,
+    stats: &mut Stats,
+) -> String {
+    if is_html(ext) {
+        minifier::minify_html(source, options, warnings, stats)
     } else {
         source.to_owned()
     }
 }
 
-fn resolve_template_path(path: &str) -> Result<PathBuf, String> {
-    let raw = Path::new(path);
-    if raw.is_absolute() && raw.is_file() {
-        return Ok(raw.to_path_buf());
+/// Checks a template and composes it with the templates it pulls in. HTML
+/// comments and elements are checked in the composed template, and reported
+/// at their position in the file they come from.
+fn load_source(
+    source: &str,
+    origin_path: Option<&Path>,
+    origin: &str,
+    html: bool,
+    args: &MacroArgs,
+    resolver: &mut Resolver<'_>,
+) -> Result<String, String> {
+    check_template(source, origin, false, resolver.syntax)?;
+    let composed = compose_template(source, origin_path, args, resolver)?;
+    if html && let Err(diagnostic) = minifier::check_template(&composed, resolver.syntax, true) {
+        if composed == source {
+            let (message, line, column) = locate(source, diagnostic);
+            return Err(format!("{message} at {origin}:{line}:{column}"));
+        }
+        return Err(trace_composed_error(
+            source,
+            origin_path,
+            origin,
+            args,
+            resolver,
+            diagnostic,
+            &composed,
+        ));
+    }
+
+    Ok(composed)
+}
+
+/// Where a marked HTML comment or element starts in its template file.
+struct Mark {
+    origin: String,
+    line: usize,
+    column: usize,
+}
+
+/// Reports a failed check of a composed template at the position in the
+/// template file that the reported comment or element comes from. The
+/// template is composed again with each start of an HTML comment,
+/// `<script>`, or `<style>` marked by an Askama comment naming its
+/// position, since that is what the check reports beyond Askama tags, which
+/// are checked in each file on their own.
+fn trace_composed_error(
+    source: &str,
+    origin_path: Option<&Path>,
+    origin: &str,
+    args: &MacroArgs,
+    resolver: &mut Resolver<'_>,
+    diagnostic: Diagnostic,
+    composed: &str,
+) -> String {
+    let syntax = resolver.syntax;
+    let mut marks = Vec::new();
+    let marked = mark_html_starts(source, origin, syntax, &mut marks);
+    resolver.marks = Some(marks);
+    let marked = compose_template(&marked, origin_path, args, resolver);
+    let marks = resolver.marks.take().unwrap_or_default();
+
+    let traced = marked.ok().and_then(|marked| {
+        let Diagnostic { offset, message } =
+            minifier::check_template(&marked, syntax, true).err()?;
+        let (_, index) = marked[..offset]
+            .strip_suffix(syntax.comment_end.as_str())?
+            .rsplit_once(&format!("{}@", syntax.comment_start))?;
+        let mark = marks.get(index.parse::<usize>().ok()?)?;
+        Some(format!(
+            "{message} at {}:{}:{}",
+            mark.origin, mark.line, mark.column
+        ))
+    });
+    traced.unwrap_or_else(|| {
+        let (message, line, column) = locate(composed, diagnostic);
+        format!("{message} at line {line}, column {column} of the template composed from {origin}")
+    })
+}
+
+/// Puts an Askama comment naming the position in `origin` before each start
+/// of an HTML comment, `<script>`, or `<style>` outside Askama tags.
+fn mark_html_starts(source: &str, origin: &str, syntax: &Syntax, marks: &mut Vec<Mark>) -> String {
+    let tags: Vec<_> = minifier::askama_tags(source, syntax)
+        .map(|tag| tag.start..tag.end)
+        .collect();
+    let mut result = String::with_capacity(source.len());
+    let mut copied = 0;
+    for (start, _) in source.match_indices('<') {
+        let rest = &source[start..];
+        let opens = ["<!--", "<script", "<style"].iter().any(|open| {
+            rest.get(..open.len())
+                .is_some_and(|prefix| prefix.eq_ignore_ascii_case(open))
+        });
+        if !opens || tags.iter().any(|tag| tag.contains(&start)) {
+            continue;
+        }
+
+        let (line, column) = line_column(source, start);
+        result.push_str(&source[copied..start]);
+        result.push_str(&format!(
+            "{}@{}{}",
+            syntax.comment_start,
+            marks.len(),
+            syntax.comment_end
+        ));
+        marks.push(Mark {
+            origin: origin.to_owned(),
+            line,
+            column,
+        });
+        copied = start;
+    }
+
+    result.push_str(&source[copied..]);
+    result
+}
+
+fn compose_template(
+    source: &str,
+    origin: Option<&Path>,
+    args: &MacroArgs,
+    resolver: &mut Resolver<'_>,
+) -> Result<String, String> {
+    let mut source = inline_includes(source, origin, resolver)?;
+    if args.flatten_extends {
+        source = flatten_extends(&source, origin, resolver)?;
+    }
+
+    inline_imports(&source, origin, resolver)
+}
+
+fn read_template(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path)
+        .map_err(|error| format!("failed to read template `{}`: {error}", path.display()))
+}
+
+/// Reports what the minifier would otherwise read to the end of a template,
+/// at its line and column in `origin`.
+fn check_template(source: &str, origin: &str, html: bool, syntax: &Syntax) -> Result<(), String> {
+    minifier::check_template(source, syntax, html).map_err(|diagnostic| {
+        let (message, line, column) = locate(source, diagnostic);
+        format!("{message} at {origin}:{line}:{column}")
+    })
+}
+
+/// Returns the message of a diagnostic with its line and column in `source`.
+fn locate(source: &str, Diagnostic { offset, message }: Diagnostic) -> (String, usize, usize) {
+    let (line, column) = line_column(source, offset);
+    (message, line, column)
+}
+
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    let line = before.matches('\n').count() + 1;
+    let column = before[line_start..].chars().count() + 1;
+    (line, column)
+}
+
+fn is_html(ext: &str) -> bool {
+    matches!(ext.to_ascii_lowercase().as_str(), "html" | "htm")
+}
+
+/// Builds an Askama comment that carries whitespace control markers over to
+/// inlined source, e.g. `{#- #}` for a tag that started with `{%-`.
+fn whitespace_comment(syntax: &Syntax, before: Option<char>, after: Option<char>) -> String {
+    if before.is_none() && after.is_none() {
+        return String::new();
     }
 
-    let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR")
-        .map(PathBuf::from)
-        .ok_or_else(|| "CARGO_MANIFEST_DIR is not set".to_string())?;
-    let candidates = [
-        manifest_dir.join(raw),
-        manifest_dir.join("templates").join(raw),
-    ];
-
-    candidates
-        .iter()
-        .find(|candidate| candidate.is_file())
-        .cloned()
-        .ok_or_else(|| {
-            let tried = candidates
-                .iter()
-                .map(|candidate| format!("`{}`", candidate.display()))
-                .collect::<Vec<_>>()
-                .join(", ");
-            format!("template `{path}` was not found; tried {tried}")
-        })
+    let before = before.map(String::from).unwrap_or_default();
+    let after = after.map(String::from).unwrap_or_default();
+    format!(
+        "{}{before} {after}{}",
+        syntax.comment_start, syntax.comment_end
+    )
 }
 
 fn extension_from_path(path: &Path) -> Option<String> {
@@ -0,0 +1,333 @@
+use super::{Resolver, inline_includes, whitespace_comment};
+use crate::minifier::{Syntax, TagKind, askama_tags, parse_string_literal};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+type Markers = (Option<char>, Option<char>);
+
+/// Replaces an `{% extends %}` chain with a single template: the root layout
+/// with every `{% block %}` filled in by its most derived definition.
+///
+/// Every parent template is tracked by `resolver`.
+pub(super) fn flatten_extends(
+    source: &str,
+    origin: Option<&Path>,
+    resolver: &mut Resolver<'_>,
+) -> Result<String, String> {
+    let mut levels = vec![
+        Level::parse(source.to_owned(), resolver.syntax)
+            .map_err(|message| in_file(message, origin))?,
+    ];
+    let mut stack: Vec<PathBuf> = origin.map(Path::to_path_buf).into_iter().collect();
+    let mut origin = origin.map(Path::to_path_buf);
+
+    while let Some(parent) = levels.last().and_then(|level| level.extends.clone()) {
+        let resolved = resolver.resolve(&parent, origin.as_deref())?;
+        if stack.contains(&resolved) {
+            return Err(format!(
+                "template `{}` extends itself recursively",
+                resolved.display()
+            ));
+        }
+
+        let source = resolver.read(&resolved)?;
+        let source = inline_includes(&source, Some(&resolved), resolver)?;
+        levels.push(
+            Level::parse(source, resolver.syntax)
+                .map_err(|message| in_file(message, Some(&resolved)))?,
+        );
+        resolver.track(&resolved);
+        stack.push(resolved.clone());
+        origin = Some(resolved);
+    }
+
+    if levels.len() == 1 {
+        return Ok(source.to_owned());
+    }
+
+    let root = levels.len() - 1;
+    let mut result = String::with_capacity(levels[root].source.len());
+
+    // Macros and imports of child templates stay available to their blocks.
+    for level in levels[..root].iter().rev() {
+        for range in &level.preserved {
+            result.push_str(&level.source[range.clone()]);
+        }
+    }
+
+    let mut flattener = Flattener {
+        levels: &levels,
+        syntax: resolver.syntax,
+        emitted: Vec::new(),
+    };
+    flattener.render(root, 0..levels[root].source.len(), None, &mut result)?;
+
+    Ok(result)
+}
+
+fn in_file(message: String, path: Option<&Path>) -> String {
+    match path {
+        Some(path) => format!("{message} in `{}`", path.display()),
+        None => message,
+    }
+}
+
+struct Level {
+    source: String,
+    extends: Option<String>,
+    blocks: Vec<Block>,
+    preserved: Vec<Range<usize>>,
+}
+
+struct Block {
+    name: String,
+    outer: Range<usize>,
+    body: Range<usize>,
+    open: Markers,
+    close: Markers,
+}
+
+struct OpenBlock {
+    name: String,
+    start: usize,
+    body_start: usize,
+    open: Markers,
+}
+
+impl Level {
+    fn parse(source: String, syntax: &Syntax) -> Result<Self, String> {
+        let mut extends = None;
+        let mut blocks = Vec::new();
+        let mut preserved = Vec::new();
+        let mut open_blocks: Vec<OpenBlock> = Vec::new();
+        let mut open_macro = None;
+
+        for tag in askama_tags(&source, syntax) {
+            let Some(block) = tag.block() else {
+                continue;
+            };
+
+            match block.keyword {
+                "extends" if extends.is_none() && open_blocks.is_empty() => {
+                    let path = parse_string_literal(block.rest).ok_or_else(|| {
+                        format!(
+                            "expected a string literal path in `{}`",
+                            &source[tag.start..tag.end]
+                        )
+                    })?;
+                    extends = Some(path);
+                }
+                "block" => open_blocks.push(OpenBlock {
+                    name: block.rest.to_owned(),
+                    start: tag.start,
+                    body_start: tag.end,
+                    open: (block.open_marker, block.close_marker),
+                }),
+                "endblock" => {
+                    let open = open_blocks
+                        .pop()
+                        .ok_or_else(|| "unexpected `endblock` without `block`".to_string())?;
+                    blocks.push(Block {
+                        name: open.name,
+                        outer: open.start..tag.end,
+                        body: open.body_start..tag.start,
+                        open: open.open,
+                        close: (block.open_marker, block.close_marker),
+                    });
+                }
+                "import" if open_blocks.is_empty() && open_macro.is_none() => {
+                    preserved.push(tag.start..tag.end);
+                }
+                "macro" if open_blocks.is_empty() => open_macro = Some(tag.start),
+                "endmacro" => {
+                    if let Some(start) = open_macro.take() {
+                        preserved.push(start..tag.end);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(open) = open_blocks.pop() {
+            return Err(format!("block `{}` is not closed", open.name));
+        }
+
+        blocks.sort_by_key(|block| block.outer.start);
+
+        Ok(Self {
+            source,
+            extends,
+            blocks,
+            preserved,
+        })
+    }
+}
+
+struct Flattener<'a> {
+    levels: &'a [Level],
+    syntax: &'a Syntax,
+    emitted: Vec<&'a str>,
+}
+
+impl<'a> Flattener<'a> {
+    fn render(
+        &mut self,
+        level: usize,
+        range: Range<usize>,
+        current: Option<(&'a str, usize)>,
+        result: &mut String,
+    ) -> Result<(), String> {
+        let levels = self.levels;
+        let mut copied = range.start;
+
+        for block in &levels[level].blocks {
+            if block.outer.start < copied || block.outer.end > range.end {
+                continue;
+            }
+
+            self.render_text(level, copied..block.outer.start, current, result)?;
+            self.render_block(block, result)?;
+            copied = block.outer.end;
+        }
+
+        self.render_text(level, copied..range.end, current, result)
+    }
+
+    /// Renders the most derived definition of `placement` where the root
+    /// layout (or an override) places it. Askama rejects a block name used
+    /// twice, so only the first placement keeps its `{% block %}` tags.
+    fn render_block(&mut self, placement: &'a Block, result: &mut String) -> Result<(), String> {
+        let syntax = self.syntax;
+        let (level, definition) = self
+            .definition(&placement.name, 0)
+            .expect("a placed block always has a definition");
+        let tagged = !self.emitted.contains(&placement.name.as_str());
+
+        if tagged {
+            self.emitted.push(&placement.name);
+            result.push_str(&block_tag(
+                syntax,
+                placement.open.0,
+                &format!("block {}", placement.name),
+                definition.open.1,
+            ));
+        } else {
+            result.push_str(&whitespace_comment(
+                syntax,
+                placement.open.0,
+                definition.open.1,
+            ));
+        }
+
+        self.render(
+            level,
+            definition.body.clone(),
+            Some((&placement.name, level)),
+            result,
+        )?;
+
+        if tagged {
+            result.push_str(&block_tag(
+                syntax,
+                definition.close.0,
+                "endblock",
+                placement.close.1,
+            ));
+        } else {
+            result.push_str(&whitespace_comment(
+                syntax,
+                definition.close.0,
+                placement.close.1,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Copies template text, replacing `{{ super() }}` inside a block with
+    /// the next definition of that block up the chain.
+    fn render_text(
+        &mut self,
+        level: usize,
+        range: Range<usize>,
+        current: Option<(&'a str, usize)>,
+        result: &mut String,
+    ) -> Result<(), String> {
+        let levels = self.levels;
+        let syntax = self.syntax;
+        let text = &levels[level].source[range];
+        let mut copied = 0;
+
+        if let Some((name, current_level)) = current {
+            for tag in askama_tags(text, syntax) {
+                if tag.kind != TagKind::Expr {
+                    continue;
+                }
+
+                let content = tag.content();
+                if content.content.replace(char::is_whitespace, "") != "super()" {
+                    continue;
+                }
+
+                let (parent_level, parent) =
+                    self.definition(name, current_level + 1).ok_or_else(|| {
+                        format!(
+                            "`super()` is used in block `{name}`, but no parent template defines it"
+                        )
+                    })?;
+
+                result.push_str(&text[copied..tag.start]);
+                result.push_str(&whitespace_comment(
+                    syntax,
+                    content.open_marker,
+                    parent.open.1,
+                ));
+                self.render(
+                    parent_level,
+                    parent.body.clone(),
+                    Some((name, parent_level)),
+                    result,
+                )?;
+                result.push_str(&whitespace_comment(
+                    syntax,
+                    parent.close.0,
+                    content.close_marker,
+                ));
+                copied = tag.end;
+            }
+        }
+
+        result.push_str(&text[copied..]);
+        Ok(())
+    }
+
+    fn definition(&self, name: &str, from: usize) -> Option<(usize, &'a Block)> {
+        let levels = self.levels;
+
+        levels
+            .iter()
+            .enumerate()
+            .skip(from)
+            .find_map(|(index, level)| {
+                level
+                    .blocks
+                    .iter()
+                    .find(|block| block.name == name)
+                    .map(|block| (index, block))
+            })
+    }
+}
+
+fn block_tag(
+    syntax: &Syntax,
+    open_marker: Option<char>,
+    content: &str,
+    close_marker: Option<char>,
+) -> String {
+    let open_marker = open_marker.map(String::from).unwrap_or_default();
+    let close_marker = close_marker.map(String::from).unwrap_or_default();
+    format!(
+        "{}{open_marker} {content} {close_marker}{}",
+        syntax.block_start, syntax.block_end
+    )
+}
@@ -0,0 +1,193 @@
+use super::{Resolver, inline_includes, whitespace_comment};
+use crate::minifier::{Syntax, askama_tags, identifier_len, parse_string_literal};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Replaces every `{% import "..." as scope %}` with the macros defined in
+/// the imported template, so they are minified with the importing template.
+///
+/// Imported macros are renamed to `scope__name` and `{% call scope::name() %}`
+/// is rewritten to match. Every imported file is tracked by `resolver`.
+pub(super) fn inline_imports(
+    source: &str,
+    origin: Option<&Path>,
+    resolver: &mut Resolver<'_>,
+) -> Result<String, String> {
+    let mut stack = origin.map(Path::to_path_buf).into_iter().collect();
+    rewrite_macros(source, origin, None, resolver, &mut stack)
+}
+
+/// Rewrites imports and macro calls of one template. With a `library` prefix
+/// only the template's macro definitions are kept, renamed with that prefix.
+fn rewrite_macros(
+    source: &str,
+    origin: Option<&Path>,
+    library: Option<&str>,
+    resolver: &mut Resolver<'_>,
+    stack: &mut Vec<PathBuf>,
+) -> Result<String, String> {
+    let syntax = resolver.syntax;
+    let own_macros = if library.is_some() {
+        macro_names(source, syntax)
+    } else {
+        Vec::new()
+    };
+    let mut scopes = Vec::new();
+    for tag in askama_tags(source, syntax) {
+        if let Some(block) = tag.block().filter(|block| block.keyword == "import")
+            && let Some((_, scope)) = parse_import(block.rest)
+        {
+            scopes.push((scope, format!("{}{scope}__", library.unwrap_or_default())));
+        }
+    }
+
+    let mut result = String::with_capacity(source.len());
+    let mut copied = 0;
+    let mut in_macro = false;
+
+    for tag in askama_tags(source, syntax) {
+        let Some(block) = tag.block() else {
+            continue;
+        };
+        let emitting = library.is_none() || in_macro;
+
+        match block.keyword {
+            "import" if !in_macro => {
+                let (path, scope) = parse_import(block.rest).ok_or_else(|| {
+                    format!(
+                        "expected `import \"...\" as scope` in `{}`",
+                        &source[tag.start..tag.end]
+                    )
+                })?;
+                let prefix = format!("{}{scope}__", library.unwrap_or_default());
+                let resolved = resolver.resolve(&path, origin)?;
+                if stack.contains(&resolved) {
+                    return Err(format!(
+                        "template `{}` imports itself recursively",
+                        resolved.display()
+                    ));
+                }
+
+                let imported = resolver.read(&resolved)?;
+                let imported = inline_includes(&imported, Some(&resolved), resolver)?;
+                stack.push(resolved.clone());
+                let macros =
+                    rewrite_macros(&imported, Some(&resolved), Some(&prefix), resolver, stack)?;
+                stack.pop();
+                resolver.track(&resolved);
+
+                if library.is_none() {
+                    result.push_str(&source[copied..tag.start]);
+                    result.push_str(&whitespace_comment(syntax, block.open_marker, None));
+                    result.push_str(&macros);
+                    result.push_str(&whitespace_comment(syntax, None, block.close_marker));
+                } else {
+                    result.push_str(&macros);
+                }
+                copied = tag.end;
+            }
+            "call" => {
+                let Some((range, scope, name)) = call_target(block.rest) else {
+                    continue;
+                };
+                let renamed = match scope {
+                    None | Some("self") => library
+                        .filter(|_| own_macros.contains(&name))
+                        .map(|prefix| format!("{prefix}{name}")),
+                    Some(scope) => scopes
+                        .iter()
+                        .find(|(known, _)| *known == scope)
+                        .map(|(_, prefix)| format!("{prefix}{name}")),
+                };
+                let Some(renamed) = renamed else {
+                    continue;
+                };
+
+                if emitting {
+                    result.push_str(&source[copied..block.rest_start + range.start]);
+                    result.push_str(&renamed);
+                }
+                copied = block.rest_start + range.end;
+            }
+            "macro" => {
+                in_macro = true;
+                if let Some(prefix) = library {
+                    let name_end = block.rest_start + identifier_len(block.rest);
+                    result.push_str(&source[tag.start..block.rest_start]);
+                    result.push_str(prefix);
+                    result.push_str(&source[block.rest_start..name_end]);
+                    copied = name_end;
+                }
+            }
+            "endmacro" => {
+                in_macro = false;
+                if let Some(prefix) = library {
+                    if !block.rest.is_empty() {
+                        result.push_str(&source[copied..block.rest_start]);
+                        result.push_str(prefix);
+                        copied = block.rest_start;
+                    }
+                    result.push_str(&source[copied..tag.end]);
+                    copied = tag.end;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if library.is_none() {
+        result.push_str(&source[copied..]);
+    }
+
+    Ok(result)
+}
+
+fn macro_names<'a>(source: &'a str, syntax: &'a Syntax) -> Vec<&'a str> {
+    askama_tags(source, syntax)
+        .filter_map(|tag| tag.block())
+        .filter(|block| block.keyword == "macro")
+        .map(|block| &block.rest[..identifier_len(block.rest)])
+        .collect()
+}
+
+/// Splits `"path" as scope` into the template path and the scope name.
+fn parse_import(rest: &str) -> Option<(String, &str)> {
+    let scope = rest.rsplit(char::is_whitespace).next()?;
+    let path = rest[..rest.len() - scope.len()]
+        .trim_end()
+        .strip_suffix("as")?;
+    if !path.ends_with(char::is_whitespace) || identifier_len(scope) != scope.len() {
+        return None;
+    }
+
+    Some((parse_string_literal(path.trim_end())?, scope))
+}
+
+/// Locates the `scope::name` or `name` macro path of a `{% call %}` tag,
+/// skipping caller parameters such as `{% call(item) list() %}`.
+fn call_target(rest: &str) -> Option<(Range<usize>, Option<&str>, &str)> {
+    let mut start = 0;
+    if rest.starts_with('(') {
+        start = rest.find(')')? + 1;
+    }
+    start += rest[start..].len() - rest[start..].trim_start().len();
+
+    let path = &rest[start..];
+    let first_len = identifier_len(path);
+    if first_len == 0 {
+        return None;
+    }
+
+    let first = &path[..first_len];
+    let Some(after_scope) = path[first_len..].trim_start().strip_prefix("::") else {
+        return Some((start..start + first_len, None, first));
+    };
+    let name = after_scope.trim_start();
+    let name_len = identifier_len(name);
+    if name_len == 0 {
+        return None;
+    }
+
+    let end = start + (path.len() - name.len()) + name_len;
+    Some((start..end, Some(first), &name[..name_len]))
+}
@@ -0,0 +1,67 @@
+use super::{Resolver, whitespace_comment};
+use crate::minifier::{askama_tags, parse_string_literal};
+use std::path::{Path, PathBuf};
+
+/// Replaces every `{% include "..." %}` with the included template source so
+/// partials are minified together with the template that uses them.
+///
+/// Every inlined file is tracked by `resolver` once.
+pub(super) fn inline_includes(
+    source: &str,
+    origin: Option<&Path>,
+    resolver: &mut Resolver<'_>,
+) -> Result<String, String> {
+    let mut stack = origin.map(Path::to_path_buf).into_iter().collect();
+    inline_nested_includes(source, origin, resolver, &mut stack)
+}
+
+fn inline_nested_includes(
+    source: &str,
+    origin: Option<&Path>,
+    resolver: &mut Resolver<'_>,
+    stack: &mut Vec<PathBuf>,
+) -> Result<String, String> {
+    let syntax = resolver.syntax;
+    let mut result = String::with_capacity(source.len());
+    let mut copied = 0;
+
+    for tag in askama_tags(source, syntax) {
+        let Some(block) = tag.block() else {
+            continue;
+        };
+
+        if block.keyword != "include" {
+            continue;
+        }
+
+        let path = parse_string_literal(block.rest).ok_or_else(|| {
+            format!(
+                "expected a string literal path in `{}`",
+                &source[tag.start..tag.end]
+            )
+        })?;
+        let resolved = resolver.resolve(&path, origin)?;
+        if stack.contains(&resolved) {
+            return Err(format!(
+                "template `{}` includes itself recursively",
+                resolved.display()
+            ));
+        }
+
+        let included = resolver.read(&resolved)?;
+        stack.push(resolved.clone());
+        let included = inline_nested_includes(&included, Some(&resolved), resolver, stack)?;
+        stack.pop();
+
+        resolver.track(&resolved);
+
+        result.push_str(&source[copied..tag.start]);
+        result.push_str(&whitespace_comment(syntax, block.open_marker, None));
+        result.push_str(&included);
+        result.push_str(&whitespace_comment(syntax, None, block.close_marker));
+        copied = tag.end;
+    }
+
+    result.push_str(&source[copied..]);
+    Ok(result)
+}
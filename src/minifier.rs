@@ -1,7 +1,107 @@
+mod attributes;
+mod check;
 mod css;
 mod html;
 mod js;
+mod lexer;
+mod optional_tags;
 mod template;
+mod tokenizer;
 mod util;
 
+pub(crate) use check::{Diagnostic, check_template, inline_elements};
 pub(crate) use html::minify_html;
+pub(crate) use template::{
+    Syntax, TagKind, Whitespace, askama_tags, identifier_len, parse_string_literal,
+};
+
+/// How Askama reads the template being minified.
+pub(crate) struct Options {
+    pub(crate) syntax: Syntax,
+    /// Askama's whitespace handling for tags without a control marker.
+    pub(crate) whitespace: Whitespace,
+    /// Drops whitespace around `{% %}` tags between block-level elements.
+    pub(crate) strip_block_whitespace: bool,
+    /// Drops whitespace next to the tags of block-level elements, where
+    /// browsers do not render it.
+    pub(crate) remove_block_whitespace: bool,
+    /// Leaves out start and end tags that the HTML spec lets browsers imply
+    /// from the tag that follows.
+    pub(crate) omit_optional_tags: bool,
+    /// Writes attribute values without quotes where HTML allows it, or in the
+    /// quote character that needs the fewest escapes.
+    pub(crate) remove_attribute_quotes: bool,
+    /// Writes boolean attributes such as `disabled="disabled"` as their name.
+    pub(crate) collapse_boolean_attributes: bool,
+    /// Drops attributes set to the value browsers assume without them.
+    pub(crate) remove_redundant_attributes: bool,
+    /// Elements treated as block-level in addition to the built-in ones.
+    pub(crate) block_elements: Vec<String>,
+    /// Elements treated as inline even when they are block-level by default.
+    pub(crate) inline_elements: Vec<String>,
+    /// Keeps every line break, leaving whitespace control to Askama, so
+    /// positions in Askama's errors match the original template lines.
+    pub(crate) preserve_lines: bool,
+}
+
+/// Bytes removed from the parts of an HTML template, for the build report.
+/// Everything else that was removed is counted as HTML.
+#[derive(Default)]
+pub(crate) struct Stats {
+    /// Saved in `<style>` content, including CSS comments.
+    pub(crate) css_saved: usize,
+    /// Saved in `<script>` content, including JavaScript comments.
+    pub(crate) js_saved: usize,
+    /// Saved by removing HTML comments.
+    pub(crate) comments_saved: usize,
+    /// The minifier used for the `<style>` content, if there was any.
+    pub(crate) css_backend: Option<CssBackend>,
+}
+
+impl Stats {
+    fn record_css(&mut self, backend: CssBackend) {
+        self.css_backend = match self.css_backend {
+            Some(used) if used != backend => Some(CssBackend::Mixed),
+            _ => Some(backend),
+        };
+    }
+}
+
+/// Which CSS minifier handled the `<style>` content of a template.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CssBackend {
+    Conservative,
+    #[cfg(feature = "advanced-css")]
+    Lightningcss,
+    /// `lightningcss` for some `<style>` blocks, and the conservative
+    /// minifier for the ones it skipped or could not parse.
+    Mixed,
+}
+
+impl CssBackend {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Conservative => "conservative",
+            #[cfg(feature = "advanced-css")]
+            Self::Lightningcss => "lightningcss",
+            Self::Mixed => "mixed",
+        }
+    }
+}
+
+impl Options {
+    /// Returns the whitespace handling Askama applies next to a tag side
+    /// with the given control `marker`.
+    fn whitespace_for(&self, marker: Option<char>) -> Whitespace {
+        marker
+            .and_then(Whitespace::from_marker)
+            .unwrap_or(self.whitespace)
+    }
+
+    /// Whether whitespace next to a tag side with `marker` must be copied
+    /// verbatim rather than collapsed: `+` asks for it explicitly, and
+    /// minimized whitespace is already reduced to a single space or newline.
+    fn keeps_whitespace(&self, marker: Option<char>) -> bool {
+        marker == Some('+') || self.whitespace_for(marker) == Whitespace::Minimize
+    }
+}
@@ -0,0 +1,227 @@
+use super::template::askama_tags;
+use super::tokenizer::{Tag, TagPart};
+use super::{Options, Whitespace};
+use std::borrow::Cow;
+
+/// Characters that end an unquoted attribute value or make it invalid.
+const NEEDS_QUOTES: &[char] = &['"', '\'', '=', '<', '>', '`'];
+
+/// Entities that stand for a double quote, as written in templates.
+const DOUBLE_QUOTE_ENTITIES: &[&str] = &["&quot;", "&#34;", "&#x22;"];
+
+/// Entities that stand for a single quote, as written in templates.
+const SINGLE_QUOTE_ENTITIES: &[&str] = &["&apos;", "&#39;", "&#x27;"];
+
+/// Attributes whose presence alone turns them on, so an empty value or their
+/// own name as the value can be left out.
+const BOOLEAN_ATTRIBUTES: &[&str] = &[
+    "allowfullscreen",
+    "async",
+    "autofocus",
+    "autoplay",
+    "checked",
+    "controls",
+    "default",
+    "defer",
+    "disabled",
+    "formnovalidate",
+    "hidden",
+    "inert",
+    "ismap",
+    "itemscope",
+    "loop",
+    "multiple",
+    "muted",
+    "nomodule",
+    "novalidate",
+    "open",
+    "playsinline",
+    "readonly",
+    "required",
+    "reversed",
+    "selected",
+];
+
+/// Attribute values that browsers assume when the attribute is missing, by
+/// element, attribute and value.
+const DEFAULT_VALUES: &[(&str, &str, &str)] = &[
+    ("form", "method", "get"),
+    ("input", "type", "text"),
+    ("link", "type", "text/css"),
+    ("script", "type", "text/javascript"),
+    ("style", "type", "text/css"),
+];
+
+pub(super) enum SimplifiedAttribute<'a> {
+    /// A boolean attribute, written as its bare name.
+    Collapsed(&'a str),
+    /// An attribute with its default value, dropped along with the
+    /// whitespace before it.
+    Removed,
+}
+
+/// Simplifies the attribute that starts at part `index` of the start tag
+/// `tag`, returning the end of its parts. Only attributes written out in the
+/// template are simplified: their name and value contain no Askama tags, and
+/// whitespace or the end of the tag separates them from the rest of it.
+/// Default values are only removed from tags without Askama tags.
+pub(super) fn simplify_attribute<'a>(
+    tag: &Tag<'a>,
+    index: usize,
+    options: &Options,
+) -> Option<(usize, SimplifiedAttribute<'a>)> {
+    if !(options.collapse_boolean_attributes || options.remove_redundant_attributes)
+        || tag.open != "<"
+        || !matches!(
+            index.checked_sub(1).map(|index| &tag.parts[index]),
+            Some(TagPart::Whitespace(_))
+        )
+    {
+        return None;
+    }
+    let TagPart::Text(text) = tag.parts.get(index)? else {
+        return None;
+    };
+    let (name, value, end) = match text.split_once('=') {
+        Some((name, "")) => match tag.parts.get(index + 1)? {
+            TagPart::Quoted(quoted)
+                if quoted.len() >= 2
+                    && quoted.ends_with(&quoted[..1])
+                    && askama_tags(quoted, &options.syntax).next().is_none() =>
+            {
+                (name, Some(&quoted[1..quoted.len() - 1]), index + 2)
+            }
+            _ => return None,
+        },
+        Some((name, value)) => (name, Some(value), index + 1),
+        None => (*text, None, index + 1),
+    };
+    if name.is_empty()
+        || !(matches!(tag.parts.get(end), Some(TagPart::Text("/")))
+            || separated_after(tag, end - 1, options))
+    {
+        return None;
+    }
+
+    let is = |names: &[&str]| {
+        names
+            .iter()
+            .any(|candidate| candidate.eq_ignore_ascii_case(name))
+    };
+    if options.collapse_boolean_attributes
+        && !tag.name.contains('-')
+        && is(BOOLEAN_ATTRIBUTES)
+        && value.is_some_and(|value| value.is_empty() || value.eq_ignore_ascii_case(name))
+    {
+        return Some((end, SimplifiedAttribute::Collapsed(name)));
+    }
+    // An Askama tag elsewhere in the tag can render the same attribute, and
+    // browsers use the first one, so the default has to stay.
+    let redundant = !tag
+        .parts
+        .iter()
+        .any(|part| matches!(part, TagPart::Askama(_)))
+        && DEFAULT_VALUES.iter().any(|(element, attribute, default)| {
+            tag.is(element)
+                && attribute.eq_ignore_ascii_case(name)
+                && value.is_some_and(|value| value.eq_ignore_ascii_case(default))
+        });
+    (options.remove_redundant_attributes && redundant)
+        .then_some((end, SimplifiedAttribute::Removed))
+}
+
+/// Whether the part of `tag` at `index` stays apart from what follows it in
+/// the rendered tag: it ends the tag or is followed by whitespace that the
+/// next Askama tag does not suppress.
+pub(super) fn separated_after(tag: &Tag<'_>, index: usize, options: &Options) -> bool {
+    match tag.parts.get(index + 1) {
+        None => true,
+        Some(TagPart::Whitespace(_)) => match tag.parts.get(index + 2) {
+            Some(TagPart::Askama(askama)) => {
+                options.whitespace_for(askama.content().open_marker) != Whitespace::Suppress
+            }
+            _ => true,
+        },
+        Some(_) => false,
+    }
+}
+
+/// Rewrites a quoted attribute value, quotes included, in its shortest form.
+/// The value is written without quotes when `unquote` is set and it is not
+/// empty and has no whitespace, quotes, `=`, `<`, `>` or backticks, and
+/// otherwise in the quote character that needs the fewest entity escapes.
+/// Unterminated values are copied verbatim.
+pub(super) fn attribute_value(quoted: &str, unquote: bool) -> Cow<'_, str> {
+    let Some(quote) = quoted.chars().next().filter(|&quote| {
+        quoted.len() >= 2 && quoted.ends_with(quote) && matches!(quote, '"' | '\'')
+    }) else {
+        return Cow::Borrowed(quoted);
+    };
+    let value = &quoted[1..quoted.len() - 1];
+    if unquote
+        && !value.is_empty()
+        && !value.contains(|ch: char| ch.is_ascii_whitespace() || NEEDS_QUOTES.contains(&ch))
+    {
+        return Cow::Borrowed(value);
+    }
+
+    let quotes = quotes(value);
+    let doubles = quotes.iter().filter(|(_, _, ch)| *ch == '"').count();
+    let singles = quotes.len() - doubles;
+    let best = match quote {
+        '"' if singles < doubles => '\'',
+        '\'' if doubles < singles => '"',
+        _ => return Cow::Borrowed(quoted),
+    };
+
+    let mut result = String::with_capacity(quoted.len());
+    result.push(best);
+    let mut copied = 0;
+    for (start, end, ch) in quotes {
+        result.push_str(&value[copied..start]);
+        if ch == best {
+            result.push_str(if ch == '"' { "&#34;" } else { "&#39;" });
+        } else {
+            result.push(ch);
+        }
+        copied = end;
+    }
+    result.push_str(&value[copied..]);
+    result.push(best);
+    Cow::Owned(result)
+}
+
+/// Finds the quote characters of an attribute value, whether written
+/// literally or as an entity, with their byte range.
+fn quotes(value: &str) -> Vec<(usize, usize, char)> {
+    let mut quotes = Vec::new();
+    let mut position = 0;
+    while let Some(offset) = value[position..].find(['"', '\'', '&']) {
+        let start = position + offset;
+        let rest = &value[start..];
+        let entity = |entities: &[&str]| {
+            entities
+                .iter()
+                .find(|entity| {
+                    rest.get(..entity.len())
+                        .is_some_and(|prefix| prefix.eq_ignore_ascii_case(entity))
+                })
+                .map(|entity| entity.len())
+        };
+        let found = match rest.as_bytes()[0] {
+            b'"' => Some((1, '"')),
+            b'\'' => Some((1, '\'')),
+            _ => entity(DOUBLE_QUOTE_ENTITIES)
+                .map(|len| (len, '"'))
+                .or_else(|| entity(SINGLE_QUOTE_ENTITIES).map(|len| (len, '\''))),
+        };
+        match found {
+            Some((len, ch)) => {
+                quotes.push((start, start + len, ch));
+                position = start + len;
+            }
+            None => position = start + 1,
+        }
+    }
+    quotes
+}
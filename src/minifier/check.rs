@@ -0,0 +1,78 @@
+use super::template::{Syntax, askama_tags};
+use super::tokenizer::{Token, tokenize};
+
+/// A template problem the minifier would otherwise swallow, found at byte
+/// `offset` of the checked source.
+pub(crate) struct Diagnostic {
+    pub(crate) offset: usize,
+    pub(crate) message: String,
+}
+
+/// Finds unterminated Askama tags and, in HTML templates, unterminated
+/// `<!--` comments and unclosed `<script>` or `<style>` elements. The
+/// minifier reads each of them to the end of the template.
+pub(crate) fn check_template(source: &str, syntax: &Syntax, html: bool) -> Result<(), Diagnostic> {
+    for tag in askama_tags(source, syntax) {
+        if tag.inner_start + tag.inner.len() == tag.end {
+            return Err(Diagnostic {
+                offset: tag.start,
+                message: format!("unterminated `{}`", &source[tag.start..tag.inner_start]),
+            });
+        }
+    }
+
+    if !html {
+        return Ok(());
+    }
+
+    let mut element_start = 0;
+    for (offset, token) in tokenize(source, syntax) {
+        match token {
+            Token::Comment {
+                terminated: false, ..
+            } => {
+                return Err(Diagnostic {
+                    offset,
+                    message: "unterminated `<!--`".to_owned(),
+                });
+            }
+            Token::StartTag(_) => element_start = offset,
+            Token::RawText {
+                element,
+                closed: false,
+                ..
+            } => {
+                return Err(Diagnostic {
+                    offset: element_start,
+                    message: format!("unclosed `<{element}>`"),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// The content of an inline `<script>` or `<style>` element.
+pub(crate) struct InlineElement<'a> {
+    pub(crate) name: &'static str,
+    pub(crate) content: &'a str,
+}
+
+/// Lists the inline `<script>` and `<style>` elements of an HTML template.
+pub(crate) fn inline_elements<'a>(source: &'a str, syntax: &'a Syntax) -> Vec<InlineElement<'a>> {
+    tokenize(source, syntax)
+        .filter_map(|(_, token)| match token {
+            Token::RawText {
+                element,
+                content,
+                closed: true,
+            } => Some(InlineElement {
+                name: element,
+                content,
+            }),
+            _ => None,
+        })
+        .collect()
+}
@@ -1,33 +1,59 @@
-use super::template::try_push_askama_template;
-use super::util::trim_trailing_space;
+use super::template::{try_push_askama_template, try_push_kept_whitespace};
+use super::util::{push_line_break, trim_trailing_space};
+use super::{CssBackend, Options};
 
 #[cfg(feature = "advanced-css")]
 use lightningcss::stylesheet::{MinifyOptions, ParserOptions, PrinterOptions, StyleSheet};
 
-pub(super) fn minify_css(css_code: &str) -> String {
+/// Minifies style content and returns it with the minifier that was used.
+/// `in_raw` is set when it starts inside a `{% raw %}` block.
+pub(super) fn minify_css(
+    css_code: &str,
+    options: &Options,
+    in_raw: bool,
+    warnings: &mut Vec<String>,
+) -> (String, CssBackend) {
+    // Only the `advanced-css` fallback is reported.
+    #[cfg(not(feature = "advanced-css"))]
+    let _ = warnings;
     #[cfg(feature = "advanced-css")]
     {
-        if !contains_askama_template(css_code) {
+        // lightningcss prints everything on one line. Styles with Askama
+        // tags are not valid CSS before rendering, so they are expected to
+        // use the conservative minifier and are not reported; a warning
+        // would only ask for changes the template cannot make.
+        if !options.preserve_lines && !contains_askama_template(css_code, options) {
             let stylesheet = StyleSheet::parse(css_code, ParserOptions::default());
 
-            if let Ok(mut sheet) = stylesheet {
-                sheet.minify(MinifyOptions::default()).ok();
-                let result = sheet.to_css(PrinterOptions {
-                    minify: true,
-                    ..PrinterOptions::default()
+            let result = stylesheet
+                .map_err(|error| error.to_string())
+                .and_then(|mut sheet| {
+                    sheet.minify(MinifyOptions::default()).ok();
+                    sheet
+                        .to_css(PrinterOptions {
+                            minify: true,
+                            ..PrinterOptions::default()
+                        })
+                        .map_err(|error| error.to_string())
                 });
 
-                if let Ok(output) = result {
-                    return output.code;
-                }
+            match result {
+                Ok(output) => return (output.code, CssBackend::Lightningcss),
+                Err(error) => warnings.push(format!(
+                    "`advanced-css` could not minify a `<style>` block ({error}); \
+                     the conservative CSS minifier was used instead"
+                )),
             }
         }
     }
 
-    minify_css_conservative(css_code)
+    (
+        minify_css_conservative(css_code, options, in_raw),
+        CssBackend::Conservative,
+    )
 }
 
-fn minify_css_conservative(css_code: &str) -> String {
+fn minify_css_conservative(css_code: &str, options: &Options, mut in_raw: bool) -> String {
     let mut result = String::with_capacity(css_code.len());
     let mut chars = css_code.chars().peekable();
     let mut in_string = false;
@@ -37,7 +63,9 @@ fn minify_css_conservative(css_code: &str) -> String {
     let mut last_significant_char = None;
 
     while let Some(ch) = chars.next() {
-        if let Some(last_ch) = try_push_askama_template(ch, &mut chars, &mut result) {
+        if let Some(last_ch) =
+            try_push_askama_template(ch, &mut chars, &mut result, options, &mut in_raw)
+        {
             last_significant_char = Some(last_ch);
             last_was_space = false;
             continue;
@@ -73,11 +101,21 @@ fn minify_css_conservative(css_code: &str) -> String {
         if ch == '/' && chars.peek() == Some(&'*') {
             chars.next();
 
+            let mut had_line_break = false;
             while let Some(comment_ch) = chars.next() {
                 if comment_ch == '*' && chars.peek() == Some(&'/') {
                     chars.next();
                     break;
                 }
+                if comment_ch == '\n' && options.preserve_lines {
+                    push_line_break(&mut result);
+                    had_line_break = true;
+                }
+            }
+
+            if had_line_break {
+                last_was_space = true;
+                continue;
             }
 
             if !css_space_is_redundant_after(last_significant_char)
@@ -90,6 +128,17 @@ fn minify_css_conservative(css_code: &str) -> String {
             continue;
         }
 
+        if try_push_kept_whitespace(ch, &mut chars, &mut result, options, in_raw) {
+            last_was_space = true;
+            continue;
+        }
+
+        if ch == '\n' && options.preserve_lines {
+            push_line_break(&mut result);
+            last_was_space = true;
+            continue;
+        }
+
         if ch.is_whitespace() {
             if !css_space_is_redundant_after(last_significant_char)
                 && !last_was_space
@@ -122,8 +171,11 @@ fn minify_css_conservative(css_code: &str) -> String {
 }
 
 #[cfg(feature = "advanced-css")]
-fn contains_askama_template(value: &str) -> bool {
-    value.contains("{{") || value.contains("{%") || value.contains("{#")
+fn contains_askama_template(value: &str, options: &Options) -> bool {
+    let syntax = &options.syntax;
+    value.contains(&syntax.expr_start)
+        || value.contains(&syntax.block_start)
+        || value.contains(&syntax.comment_start)
 }
 
 fn css_space_is_redundant_after(previous: Option<char>) -> bool {
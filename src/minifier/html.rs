@@ -1,217 +1,548 @@
+use super::attributes::{
+    SimplifiedAttribute, attribute_value, separated_after, simplify_attribute,
+};
 use super::css::minify_css;
 use super::js::minify_js;
-use super::template::try_push_askama_template;
-use super::util::trim_trailing_whitespace;
+use super::optional_tags::{OptionalTag, optional_tag};
+use super::template::{AskamaTag, TagKind, apply_whitespace_control, askama_tags};
+use super::tokenizer::{OpenElements, Tag, TagPart, Token, tokenize};
+use super::util::{push_line_break, trim_trailing_whitespace};
+use super::{Options, Stats, Whitespace};
 
-pub(crate) fn minify_html(content: &str) -> String {
+/// Minifies an HTML template. Inputs that are minified on a best-effort
+/// basis are reported in `warnings`, and the bytes saved in comments,
+/// scripts, and styles are added to `stats`.
+pub(crate) fn minify_html(
+    content: &str,
+    options: &Options,
+    warnings: &mut Vec<String>,
+    stats: &mut Stats,
+) -> String {
+    // Whitespace control removes line breaks, so with `preserve_lines` it is
+    // left to Askama, which still sees every marker.
+    let content = if options.preserve_lines {
+        if options.strip_block_whitespace {
+            warnings.push(
+                "`strip_block_whitespace` has no effect with `preserve_lines`, so whitespace \
+                 around block tags renders differently than in builds without it"
+                    .to_owned(),
+            );
+        }
+        content.to_owned()
+    } else if options.strip_block_whitespace {
+        strip_block_tag_whitespace(&apply_whitespace_control(content, options), options)
+    } else {
+        apply_whitespace_control(content, options)
+    };
+    let tokens: Vec<Token<'_>> = tokenize(&content, &options.syntax)
+        .map(|(_, token)| token)
+        .collect();
     let mut result = String::with_capacity(content.len());
-    let mut chars = content.chars().peekable();
-    let mut in_tag = false;
-    let mut in_script = false;
-    let mut in_style = false;
-    let mut in_pre = false;
-    let mut in_textarea = false;
-    let mut attr_quote = None;
+    let mut elements = OpenElements::default();
+    let mut in_raw = false;
     let mut last_was_space = false;
-    let mut tag_name = String::new();
-    let mut script_content = String::new();
-    let mut style_content = String::new();
-
-    while let Some(ch) = chars.next() {
-        if !in_tag && (in_script || in_style) {
-            if ch == '<' {
-                let is_closing_tag = (in_script && starts_with_closing_tag(&chars, "script"))
-                    || (in_style && starts_with_closing_tag(&chars, "style"));
-
-                if !is_closing_tag && in_script {
-                    script_content.push(ch);
-                    last_was_space = false;
-                    continue;
-                } else if !is_closing_tag {
-                    style_content.push(ch);
-                    last_was_space = false;
-                    continue;
+    let mut omitted: Option<OmittedTag<'_>> = None;
+
+    for (index, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Askama(tag) => {
+                result.push_str(tag.source);
+                if let Some(block) = tag.block() {
+                    in_raw = block.keyword == "raw";
                 }
-            } else {
-                let target = if in_script {
-                    &mut script_content
-                } else {
-                    &mut style_content
+                last_was_space = false;
+            }
+            Token::Text(text) if elements.contains("pre") || elements.contains("textarea") => {
+                result.push_str(text);
+                last_was_space = false;
+            }
+            Token::Text(text) => {
+                let askama = |index: Option<usize>| match index.and_then(|index| tokens.get(index))
+                {
+                    Some(Token::Askama(tag)) => Some(tag),
+                    _ => None,
                 };
+                let keep_leading = keeps_whitespace_after(askama(index.checked_sub(1)), options);
+                let keep_trailing = keeps_whitespace_before(askama(Some(index + 1)), options);
 
-                if try_push_askama_template(ch, &mut chars, target).is_some() {
-                    last_was_space = false;
-                    continue;
+                let mut text = *text;
+                let (mut leading, mut trailing) = ("", "");
+                if options.remove_block_whitespace {
+                    if !keep_leading && next_to_block_element(tokens[..index].iter().rev(), options)
+                    {
+                        let trimmed = text.trim_ascii_start();
+                        leading = &text[..text.len() - trimmed.len()];
+                        text = trimmed;
+                    }
+                    if !keep_trailing && next_to_block_element(tokens[index + 1..].iter(), options)
+                    {
+                        let trimmed = text.trim_ascii_end();
+                        trailing = &text[trimmed.len()..];
+                        text = trimmed;
+                    }
                 }
 
-                target.push(ch);
+                drop_whitespace(&mut result, leading, options, &mut last_was_space);
+                push_text(
+                    &mut result,
+                    text,
+                    keep_leading,
+                    keep_trailing,
+                    options,
+                    &mut last_was_space,
+                );
+                drop_whitespace(&mut result, trailing, options, &mut last_was_space);
+            }
+            Token::Comment { source, .. } if guards_whitespace(&tokens, index, options) => {
+                result.push_str(source);
+                last_was_space = false;
+            }
+            Token::Comment { source, .. } => {
+                let line_breaks = source.matches('\n').count();
+                if options.preserve_lines && line_breaks > 0 {
+                    stats.comments_saved += source.len() - line_breaks;
+                    for _ in 0..line_breaks {
+                        push_line_break(&mut result);
+                    }
+                    last_was_space = true;
+                } else {
+                    stats.comments_saved += source.len();
+                    last_was_space = result.ends_with(' ');
+                }
+            }
+            Token::StartTag(tag) | Token::EndTag(tag) | Token::Declaration(tag) => {
+                if let Some(previous) = omitted.take() {
+                    previous.resolve(&mut result, Some(token));
+                }
+                let start = result.len();
+                push_tag(&mut result, tag, options);
+                last_was_space = false;
+                omitted = optional_tag(token, &elements)
+                    .filter(|_| options.omit_optional_tags)
+                    .map(|tag| OmittedTag {
+                        tag,
+                        position: start,
+                        text: result.split_off(start),
+                    });
+            }
+            Token::RawText { content: "", .. } => {}
+            Token::RawText {
+                element: "script",
+                content,
+                ..
+            } => {
+                let minified = minify_js(content, options, in_raw, warnings);
+                stats.js_saved += content.len().saturating_sub(minified.len());
+                result.push_str(&minified);
+                last_was_space = false;
+            }
+            Token::RawText { content, .. } => {
+                let (minified, backend) = minify_css(content, options, in_raw, warnings);
+                stats.css_saved += content.len().saturating_sub(minified.len());
+                stats.record_css(backend);
+                result.push_str(&minified);
                 last_was_space = false;
-                continue;
             }
         }
-
-        if try_push_askama_template(ch, &mut chars, &mut result).is_some() {
-            last_was_space = false;
-            continue;
+        // Anything but whitespace between an omitted tag and the next tag,
+        // including Askama tags that only decide the next tag at render
+        // time, keeps it.
+        if omitted
+            .as_ref()
+            .is_some_and(|omitted| !result[omitted.position..].trim_ascii().is_empty())
+            && let Some(omitted) = omitted.take()
+        {
+            omitted.restore(&mut result);
         }
+        elements.update(token);
+    }
+
+    // The pending tag is placed first, as trimming can move the end of the
+    // result before its position.
+    if let Some(omitted) = omitted {
+        omitted.resolve(&mut result, None);
+    }
+    trim_trailing_whitespace(&mut result);
+    result
+}
 
-        if !in_script && !in_style && ch == '<' && starts_with_html_comment(&chars) {
-            chars.next();
-            chars.next();
-            chars.next();
+/// An optional tag left out of the result until the next tag shows whether
+/// it can be omitted.
+struct OmittedTag<'a> {
+    tag: OptionalTag<'a>,
+    /// Where the tag belongs in the result.
+    position: usize,
+    text: String,
+}
 
-            let mut comment_end = String::new();
-            for c in chars.by_ref() {
-                comment_end.push(c);
-                if comment_end.ends_with("-->") {
-                    break;
-                }
+impl OmittedTag<'_> {
+    /// Puts the tag back unless it can be omitted before `next`, the next
+    /// tag, or at the end of the template when `None`. Whitespace in between
+    /// keeps the tag as well.
+    fn resolve(self, result: &mut String, next: Option<&Token<'_>>) {
+        if result.len() != self.position || !self.tag.omissible_before(next) {
+            self.restore(result);
+        }
+    }
 
-                if comment_end.len() > 3 {
-                    let keep_from = comment_end.len() - 3;
-                    comment_end.drain(..keep_from);
-                }
-            }
-            last_was_space = result.ends_with(' ');
+    fn restore(self, result: &mut String) {
+        result.insert_str(self.position, &self.text);
+    }
+}
+
+/// Copies a tag, collapsing the whitespace between its attributes and
+/// simplifying them as `options` ask.
+fn push_tag(result: &mut String, tag: &Tag<'_>, options: &Options) {
+    result.push_str(tag.open);
+    result.push_str(tag.name);
+    let mut last_was_space = false;
+    // The end of the parts of an attribute that was simplified.
+    let mut skipped = 0;
+    for (index, part) in tag.parts.iter().enumerate() {
+        if index < skipped {
             continue;
         }
-
-        if in_tag {
-            if let Some(quote) = attr_quote {
-                result.push(ch);
-                if ch == quote {
-                    attr_quote = None;
-                }
+        match part {
+            TagPart::Whitespace(_)
+                if let Some((end, SimplifiedAttribute::Removed)) =
+                    simplify_attribute(tag, index + 1, options) =>
+            {
+                skipped = end;
+            }
+            TagPart::Text(_)
+                if let Some((end, SimplifiedAttribute::Collapsed(name))) =
+                    simplify_attribute(tag, index, options) =>
+            {
+                result.push_str(name);
                 last_was_space = false;
-                continue;
+                skipped = end;
             }
-
-            if ch == '"' || ch == '\'' {
-                attr_quote = Some(ch);
-                result.push(ch);
+            TagPart::Whitespace(whitespace) => {
+                let askama =
+                    |index: Option<usize>| match index.and_then(|index| tag.parts.get(index)) {
+                        Some(TagPart::Askama(tag)) => Some(tag),
+                        _ => None,
+                    };
+                push_text(
+                    result,
+                    whitespace,
+                    keeps_whitespace_after(askama(index.checked_sub(1)), options),
+                    keeps_whitespace_before(askama(Some(index + 1)), options),
+                    options,
+                    &mut last_was_space,
+                );
+            }
+            TagPart::Askama(askama) => {
+                result.push_str(askama.source);
                 last_was_space = false;
-                continue;
             }
-
-            if ch == '>' {
-                in_tag = false;
-                result.push(ch);
+            TagPart::Quoted(text)
+                if options.remove_attribute_quotes
+                    && tag.open == "<"
+                    && matches!(
+                        index.checked_sub(1).map(|index| &tag.parts[index]),
+                        Some(TagPart::Text(name)) if name.ends_with('=')
+                    )
+                    && askama_tags(text, &options.syntax).next().is_none() =>
+            {
+                let unquote = separated_after(tag, index, options);
+                result.push_str(&attribute_value(text, unquote));
                 last_was_space = false;
-                continue;
             }
-
-            if ch.is_whitespace() {
-                if !last_was_space {
-                    result.push(' ');
-                    last_was_space = true;
-                }
-            } else {
-                result.push(ch);
+            TagPart::Quoted(text) | TagPart::Text(text) => {
+                result.push_str(text);
                 last_was_space = false;
             }
-            continue;
         }
+    }
+    if tag.closed {
+        result.push('>');
+    }
+}
 
-        if ch == '<' {
-            in_tag = true;
-            attr_quote = None;
-            tag_name.clear();
-            result.push(ch);
-            last_was_space = false;
+/// Collapses each whitespace run of `text` to a single space, or to its line
+/// breaks with `preserve_lines`. Whitespace next to an Askama tag whose
+/// marker keeps it is copied verbatim: the leading run with `keep_leading`
+/// and the trailing run with `keep_trailing`.
+fn push_text(
+    result: &mut String,
+    text: &str,
+    keep_leading: bool,
+    keep_trailing: bool,
+    options: &Options,
+    last_was_space: &mut bool,
+) {
+    let mut text = text;
+    if keep_leading {
+        let trimmed = text.trim_ascii_start();
+        result.push_str(&text[..text.len() - trimmed.len()]);
+        text = trimmed;
+    }
+    let mut kept = "";
+    if keep_trailing {
+        let trimmed = text.trim_ascii_end();
+        kept = &text[trimmed.len()..];
+        text = trimmed;
+    }
 
-            while let Some(&next_ch) = chars.peek() {
-                if next_ch.is_whitespace() || next_ch == '>' {
-                    break;
-                }
-                if next_ch == '/' && !tag_name.is_empty() {
-                    break;
-                }
-                tag_name.push(chars.next().unwrap());
+    for ch in text.chars() {
+        if ch == '\n' && options.preserve_lines {
+            push_line_break(result);
+            *last_was_space = true;
+        } else if ch.is_whitespace() {
+            if !*last_was_space && !result.is_empty() {
+                result.push(' ');
+                *last_was_space = true;
             }
+        } else {
+            result.push(ch);
+            *last_was_space = false;
+        }
+    }
 
-            if tag_name.eq_ignore_ascii_case("/script") {
-                result.pop();
-                if !script_content.is_empty() {
-                    let minified = minify_js(&script_content);
-                    result.push_str(&minified);
-                }
-                script_content.clear();
-                in_script = false;
-                result.push('<');
-            } else if tag_name.eq_ignore_ascii_case("/style") {
-                result.pop();
-                if !style_content.is_empty() {
-                    let minified = minify_css(&style_content);
-                    result.push_str(&minified);
-                }
-                style_content.clear();
-                in_style = false;
-                result.push('<');
-            } else if tag_name.eq_ignore_ascii_case("/pre") {
-                in_pre = false;
-            } else if tag_name.eq_ignore_ascii_case("/textarea") {
-                in_textarea = false;
-            }
-
-            result.push_str(&tag_name);
-
-            if tag_name.eq_ignore_ascii_case("script") {
-                in_script = true;
-                script_content.clear();
-            } else if tag_name.eq_ignore_ascii_case("style") {
-                in_style = true;
-                style_content.clear();
-            } else if tag_name.eq_ignore_ascii_case("pre") {
-                in_pre = true;
-            } else if tag_name.eq_ignore_ascii_case("textarea") {
-                in_textarea = true;
-            }
+    if !kept.is_empty() {
+        result.push_str(kept);
+        *last_was_space = true;
+    }
+}
+
+/// Whether the comment at `index` keeps whitespace apart from an Askama tag
+/// whose marker suppresses it, as in `{{- a -}}<!-- c --> b`, so removing the
+/// comment would change the rendered text. Other comments in between are
+/// looked past.
+fn guards_whitespace(tokens: &[Token<'_>], index: usize, options: &Options) -> bool {
+    let not_comment = |token: &&Token<'_>| !matches!(token, Token::Comment { .. });
+    let before = tokens[..index].iter().rev().find(not_comment);
+    let after = tokens[index + 1..].iter().find(not_comment);
+    let suppresses = |marker| options.whitespace_for(marker) == Whitespace::Suppress;
+    match (before, after) {
+        (Some(Token::Askama(tag)), Some(Token::Text(text))) => {
+            suppresses(tag.content().close_marker)
+                && text.starts_with(|ch: char| ch.is_ascii_whitespace())
+        }
+        (Some(Token::Text(text)), Some(Token::Askama(tag))) => {
+            suppresses(tag.content().open_marker)
+                && text.ends_with(|ch: char| ch.is_ascii_whitespace())
+        }
+        _ => false,
+    }
+}
+
+/// Whether whitespace after an Askama `tag` is kept by its closing marker.
+fn keeps_whitespace_after(tag: Option<&AskamaTag<'_>>, options: &Options) -> bool {
+    tag.is_some_and(|tag| options.keeps_whitespace(tag.content().close_marker))
+}
+
+/// Whether whitespace before an Askama `tag` is kept by its opening marker.
+fn keeps_whitespace_before(tag: Option<&AskamaTag<'_>>, options: &Options) -> bool {
+    tag.is_some_and(|tag| options.keeps_whitespace(tag.content().open_marker))
+}
+
+/// Elements whose surrounding whitespace never renders outside preformatted
+/// text, so it can be dropped next to control tags with
+/// `strip_block_whitespace` and next to their tags with
+/// `remove_block_whitespace`. Besides block-level elements, these are the
+/// elements that are not laid out inline, such as `title`, `meta`, `link`,
+/// `option` and `col`.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "body",
+    "caption",
+    "col",
+    "colgroup",
+    "dd",
+    "details",
+    "dialog",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "header",
+    "hgroup",
+    "hr",
+    "html",
+    "li",
+    "link",
+    "main",
+    "menu",
+    "meta",
+    "nav",
+    "ol",
+    "optgroup",
+    "option",
+    "p",
+    "section",
+    "summary",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "title",
+    "tr",
+    "ul",
+];
+
+/// Removes the whitespace around a run of `{% %}` and `{# #}` tags when the
+/// markup on both sides of the run is a block-level element tag, so loops
+/// and conditionals between elements do not repeat a space at runtime.
+///
+/// Whitespace kept by a `+` or `~` marker is left alone, and so is the
+/// whitespace inside `<pre>`, `<textarea>`, `<script>` and `<style>`.
+fn strip_block_tag_whitespace(content: &str, options: &Options) -> String {
+    let tags: Vec<AskamaTag<'_>> = askama_tags(content, &options.syntax).collect();
+    // Askama tags inside `<script>` and `<style>` are part of their content
+    // rather than tokens of their own, so they are never listed.
+    let mut elements = OpenElements::default();
+    let mut strippable = Vec::new();
+    for (start, token) in tokenize(content, &options.syntax) {
+        if matches!(token, Token::Askama(_))
+            && !elements.contains("pre")
+            && !elements.contains("textarea")
+        {
+            strippable.push(start);
+        }
+        elements.update(&token);
+    }
+    let mut result = String::with_capacity(content.len());
+    let mut copied = 0;
+    let mut index = 0;
+
+    while index < tags.len() {
+        if tags[index].kind == TagKind::Expr {
+            index += 1;
             continue;
         }
 
-        if in_pre || in_textarea {
-            result.push(ch);
-            last_was_space = false;
-        } else if ch.is_whitespace() {
-            if !last_was_space && !result.is_empty() {
-                result.push(' ');
-                last_was_space = true;
+        let first = index;
+        while tags.get(index + 1).is_some_and(|next| {
+            next.kind != TagKind::Expr
+                && content[tags[index].end..next.start].trim_ascii().is_empty()
+        }) {
+            index += 1;
+        }
+        let run = &tags[first..=index];
+        index += 1;
+
+        let before_start = first
+            .checked_sub(1)
+            .map_or(0, |previous| tags[previous].end);
+        let after_end = tags.get(index).map_or(content.len(), |next| next.start);
+        if run
+            .iter()
+            .any(|tag| strippable.binary_search(&tag.start).is_err())
+            || !ends_with_block_element(&content[before_start..run[0].start], options)
+            || !starts_with_block_element(&content[run[run.len() - 1].end..after_end], options)
+        {
+            continue;
+        }
+
+        let mut close_marker = None;
+        for (position, tag) in run.iter().enumerate() {
+            let markers = tag.content();
+            let text = &content[copied..tag.start];
+            if options.keeps_whitespace(markers.open_marker)
+                || (position > 0 && options.keeps_whitespace(close_marker))
+            {
+                result.push_str(text);
+            } else {
+                result.push_str(text.trim_ascii_end());
             }
-        } else {
-            result.push(ch);
-            last_was_space = false;
+            result.push_str(&content[tag.start..tag.end]);
+            copied = tag.end;
+            close_marker = markers.close_marker;
+        }
+
+        if !options.keeps_whitespace(close_marker) {
+            let after = &content[copied..after_end];
+            copied += after.len() - after.trim_ascii_start().len();
         }
     }
 
-    trim_trailing_whitespace(&mut result);
+    result.push_str(&content[copied..]);
     result
 }
 
-fn starts_with_html_comment(chars: &std::iter::Peekable<std::str::Chars<'_>>) -> bool {
-    let mut lookahead = chars.clone();
+fn ends_with_block_element(text: &str, options: &Options) -> bool {
+    text.trim_ascii_end()
+        .strip_suffix('>')
+        .and_then(|tag| tag.rfind('<').map(|open| &tag[open + 1..]))
+        .is_some_and(|tag| is_block_tag(tag, options))
+}
 
-    matches!(
-        (lookahead.next(), lookahead.next(), lookahead.next()),
-        (Some('!'), Some('-'), Some('-'))
-    )
+fn starts_with_block_element(text: &str, options: &Options) -> bool {
+    text.trim_ascii_start()
+        .strip_prefix('<')
+        .is_some_and(|tag| is_block_tag(tag, options))
 }
 
-fn starts_with_closing_tag(chars: &std::iter::Peekable<std::str::Chars<'_>>, tag: &str) -> bool {
-    let mut lookahead = chars.clone();
+/// Checks the element name of an opening or closing tag without its `<`.
+fn is_block_tag(tag: &str, options: &Options) -> bool {
+    let tag = tag.strip_prefix('/').unwrap_or(tag);
+    let name = &tag[..tag
+        .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '-'))
+        .unwrap_or(tag.len())];
+
+    is_block_element(name, options)
+}
 
-    if lookahead.next() != Some('/') {
+/// Whether `name` is a block-level element, built in or configured in the
+/// `[askama-minify]` table.
+fn is_block_element(name: &str, options: &Options) -> bool {
+    let listed = |elements: &[String]| {
+        elements
+            .iter()
+            .any(|element| element.eq_ignore_ascii_case(name))
+    };
+    if listed(&options.inline_elements) {
         return false;
     }
 
-    for expected in tag.chars() {
-        let Some(actual) = lookahead.next() else {
-            return false;
-        };
+    listed(&options.block_elements)
+        || BLOCK_ELEMENTS
+            .iter()
+            .any(|element| element.eq_ignore_ascii_case(name))
+}
+
+/// Whether the token next to some text, skipping comments, is the start or
+/// end tag of a block-level element.
+fn next_to_block_element<'t, 'a: 't>(
+    mut tokens: impl Iterator<Item = &'t Token<'a>>,
+    options: &Options,
+) -> bool {
+    matches!(
+        tokens.find(|token| !matches!(token, Token::Comment { .. })),
+        Some(Token::StartTag(tag) | Token::EndTag(tag)) if is_block_element(tag.name, options)
+    )
+}
 
-        if !actual.eq_ignore_ascii_case(&expected) {
-            return false;
+/// Drops whitespace that browsers do not render, keeping its line breaks
+/// with `preserve_lines`.
+fn drop_whitespace(
+    result: &mut String,
+    whitespace: &str,
+    options: &Options,
+    last_was_space: &mut bool,
+) {
+    if options.preserve_lines {
+        for _ in whitespace.matches('\n') {
+            push_line_break(result);
+            *last_was_space = true;
         }
     }
-
-    matches!(lookahead.peek(), Some(ch) if ch.is_whitespace() || *ch == '>')
 }
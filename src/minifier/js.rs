@@ -1,7 +1,17 @@
-use super::template::try_push_askama_template;
-use super::util::trim_trailing_whitespace;
+use super::Options;
+use super::template::{try_push_askama_template, try_push_kept_whitespace};
+use super::util::{push_line_break, trim_trailing_space, trim_trailing_whitespace};
+use std::iter::Peekable;
+use std::str::Chars;
 
-pub(super) fn minify_js(js_code: &str) -> String {
+/// Minifies script content. `in_raw` is set when it starts inside a
+/// `{% raw %}` block.
+pub(super) fn minify_js(
+    js_code: &str,
+    options: &Options,
+    mut in_raw: bool,
+    warnings: &mut Vec<String>,
+) -> String {
     let mut result = String::with_capacity(js_code.len());
     let mut chars = js_code.chars().peekable();
     let mut in_string = false;
@@ -14,7 +24,9 @@ pub(super) fn minify_js(js_code: &str) -> String {
     let mut last_was_space = false;
 
     while let Some(ch) = chars.next() {
-        if let Some(last_ch) = try_push_askama_template(ch, &mut chars, &mut result) {
+        if let Some(last_ch) =
+            try_push_askama_template(ch, &mut chars, &mut result, options, &mut in_raw)
+        {
             last_char = last_ch;
             last_was_space = false;
             continue;
@@ -29,7 +41,10 @@ pub(super) fn minify_js(js_code: &str) -> String {
         if in_single_comment {
             if ch == '\n' {
                 in_single_comment = false;
-                if !last_was_space && !result.is_empty() {
+                if options.preserve_lines {
+                    push_line_break(&mut result);
+                    last_was_space = true;
+                } else if !last_was_space && !result.is_empty() {
                     result.push('\n');
                     last_was_space = true;
                 }
@@ -45,7 +60,10 @@ pub(super) fn minify_js(js_code: &str) -> String {
         }
 
         if in_multi_comment {
-            if ch == '\n' || ch == '\r' {
+            if ch == '\n' && options.preserve_lines {
+                push_line_break(&mut result);
+                last_was_space = true;
+            } else if ch == '\n' || ch == '\r' {
                 multi_comment_had_newline = true;
             } else if ch == '*' && chars.peek() == Some(&'/') {
                 in_multi_comment = false;
@@ -63,6 +81,15 @@ pub(super) fn minify_js(js_code: &str) -> String {
             continue;
         }
 
+        if !in_string && ch == '/' && starts_regex(last_char) && regex_is_ambiguous(chars.clone()) {
+            warnings.push(
+                "a regular expression literal in `<script>` contains whitespace, quotes, or \
+                 comment markers, which the JavaScript minifier does not recognize; \
+                 consider `new RegExp(\"...\")`"
+                    .to_owned(),
+            );
+        }
+
         if ch == '"' || ch == '\'' || ch == '`' {
             if !in_string {
                 in_string = true;
@@ -89,8 +116,15 @@ pub(super) fn minify_js(js_code: &str) -> String {
             continue;
         }
 
-        if ch.is_whitespace() {
-            if ch == '\n' || ch == '\r' {
+        if try_push_kept_whitespace(ch, &mut chars, &mut result, options, in_raw) {
+            last_was_space = true;
+        } else if ch.is_whitespace() {
+            if options.preserve_lines && (ch == '\n' || ch == '\r') {
+                if ch == '\n' {
+                    push_line_break(&mut result);
+                    last_was_space = true;
+                }
+            } else if ch == '\n' || ch == '\r' {
                 if !last_was_space && !result.is_empty() {
                     result.push('\n');
                     last_was_space = true;
@@ -106,10 +140,68 @@ pub(super) fn minify_js(js_code: &str) -> String {
         }
     }
 
-    trim_trailing_whitespace(&mut result);
+    if options.preserve_lines {
+        trim_trailing_space(&mut result);
+    } else {
+        trim_trailing_whitespace(&mut result);
+    }
     result
 }
 
+/// Whether a `/` after `previous` starts a regular expression literal rather
+/// than a division.
+fn starts_regex(previous: char) -> bool {
+    matches!(
+        previous,
+        '\0' | '('
+            | ','
+            | '='
+            | ':'
+            | '['
+            | '!'
+            | '&'
+            | '|'
+            | '?'
+            | '{'
+            | '}'
+            | ';'
+            | '+'
+            | '-'
+            | '*'
+            | '%'
+            | '<'
+            | '>'
+            | '~'
+            | '^'
+    )
+}
+
+/// Reads a regular expression literal after its opening `/` and reports
+/// whether it holds text the minifier treats as whitespace, a string, or a
+/// comment. Text without a closing `/` on the same line is not a literal.
+fn regex_is_ambiguous(mut chars: Peekable<Chars<'_>>) -> bool {
+    let mut literal = String::from('/');
+    let mut in_class = false;
+    while let Some(ch) = chars.next() {
+        literal.push(ch);
+        match ch {
+            '\n' | '\r' => return false,
+            '\\' => literal.extend(chars.next()),
+            '[' => in_class = true,
+            ']' => in_class = false,
+            '/' if !in_class => {
+                return literal
+                    .contains(|ch: char| ch.is_whitespace() || matches!(ch, '"' | '\'' | '`'))
+                    || literal.contains("//")
+                    || literal.contains("/*");
+            }
+            _ => {}
+        }
+    }
+
+    false
+}
+
 fn needs_js_space(previous: char, next: Option<char>) -> bool {
     matches!(previous, 'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '$')
         && matches!(next, Some(ch) if ch.is_alphanumeric() || ch == '_' || ch == '$')
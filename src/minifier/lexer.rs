@@ -0,0 +1,103 @@
+use super::template::{Syntax, TagKind};
+
+/// Follows the inside of an Askama tag one character at a time to find its
+/// end delimiter. Like Askama's parser, delimiters inside string and char
+/// literals of expressions and blocks are skipped, and comments nest.
+pub(super) struct TagLexer<'a> {
+    syntax: &'a Syntax,
+    kind: TagKind,
+    end: &'a str,
+    inner: String,
+    literal: Option<Literal>,
+    depth: usize,
+}
+
+#[derive(Clone, Copy)]
+enum Literal {
+    Str,
+    StrEscape,
+    /// Right after the opening `'`.
+    CharStart,
+    /// After one plain character, where only the closing `'` may follow.
+    CharEnd,
+    CharEscape,
+    /// The rest of an escape such as `\u{7d}`, up to the closing `'`.
+    CharEscaped,
+}
+
+impl<'a> TagLexer<'a> {
+    pub(super) fn new(syntax: &'a Syntax, kind: TagKind, end: &'a str) -> Self {
+        Self {
+            syntax,
+            kind,
+            end,
+            inner: String::new(),
+            literal: None,
+            depth: 0,
+        }
+    }
+
+    /// The characters fed so far, including the end delimiter once found.
+    pub(super) fn inner(&self) -> &str {
+        &self.inner
+    }
+
+    /// Feeds the next character and returns `true` once it completes the
+    /// end delimiter of the tag.
+    pub(super) fn push(&mut self, ch: char) -> bool {
+        self.inner.push(ch);
+
+        if let Some(literal) = self.literal
+            && self.continue_literal(literal, ch)
+        {
+            return false;
+        }
+
+        if self.kind == TagKind::Comment {
+            if self.inner.ends_with(self.syntax.comment_start.as_str()) {
+                self.depth += 1;
+                return false;
+            }
+        } else if ch == '"' {
+            self.literal = Some(Literal::Str);
+            return false;
+        } else if ch == '\'' {
+            self.literal = Some(Literal::CharStart);
+            return false;
+        }
+
+        if !self.inner.ends_with(self.end) {
+            return false;
+        }
+
+        match self.depth.checked_sub(1) {
+            Some(depth) => {
+                self.depth = depth;
+                false
+            }
+            None => true,
+        }
+    }
+
+    /// Advances through a literal and returns whether `ch` belonged to it.
+    fn continue_literal(&mut self, literal: Literal, ch: char) -> bool {
+        self.literal = match (literal, ch) {
+            (Literal::Str, '\\') => Some(Literal::StrEscape),
+            (Literal::Str, '"') => None,
+            (Literal::Str | Literal::StrEscape, _) => Some(Literal::Str),
+            (Literal::CharStart, '\\') => Some(Literal::CharEscape),
+            (Literal::CharStart, '\'') => None,
+            (Literal::CharStart, _) => Some(Literal::CharEnd),
+            (Literal::CharEscape, _) => Some(Literal::CharEscaped),
+            (Literal::CharEnd | Literal::CharEscaped, '\'') => None,
+            (Literal::CharEscaped, _) => Some(Literal::CharEscaped),
+            // A lifetime or label rather than a char literal, so `ch` is code.
+            (Literal::CharEnd, _) => {
+                self.literal = None;
+                return false;
+            }
+        };
+
+        true
+    }
+}
@@ -0,0 +1,155 @@
+use super::tokenizer::{OpenElements, TagPart, Token};
+
+/// A start or end tag that HTML allows to omit, depending on what follows.
+pub(super) enum OptionalTag<'a> {
+    Start(&'a str),
+    End {
+        name: &'a str,
+        /// The element that contains the closed one.
+        parent: Option<&'a str>,
+    },
+}
+
+/// Elements whose end tag HTML allows to omit in some contexts.
+const OPTIONAL_END_TAGS: &[&str] = &[
+    "body", "dd", "dt", "head", "html", "li", "optgroup", "option", "p", "rp", "rt", "tbody", "td",
+    "tfoot", "th", "thead", "tr",
+];
+
+/// Elements whose start tag closes an open `<p>`.
+const CLOSES_PARAGRAPH: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "details",
+    "dialog",
+    "div",
+    "dl",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hgroup",
+    "hr",
+    "main",
+    "menu",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "search",
+    "section",
+    "table",
+    "ul",
+];
+
+/// Parents whose end tag does not let a `<p>` inside them drop its own.
+const KEEPS_PARAGRAPH_END: &[&str] = &["a", "audio", "del", "ins", "map", "noscript", "video"];
+
+/// Elements whose content may start without a `<body>` start tag.
+const KEEPS_BODY_START: &[&str] = &["meta", "link", "script", "style", "template"];
+
+/// Returns the optional tag `token` is, given the `elements` open before it.
+/// Start tags are only optional without attributes, and end tags only when
+/// they close an open element that may omit them.
+pub(super) fn optional_tag<'a>(
+    token: &Token<'a>,
+    elements: &OpenElements<'a>,
+) -> Option<OptionalTag<'a>> {
+    match token {
+        Token::StartTag(tag)
+            if tag.closed
+                && is_any(tag.name, &["html", "head", "body"])
+                && tag
+                    .parts
+                    .iter()
+                    .all(|part| matches!(part, TagPart::Whitespace(_))) =>
+        {
+            Some(OptionalTag::Start(tag.name))
+        }
+        Token::EndTag(tag) if tag.closed && is_any(tag.name, OPTIONAL_END_TAGS) => {
+            let parent = elements.parent_of(tag)?;
+            Some(OptionalTag::End {
+                name: tag.name,
+                parent,
+            })
+        }
+        _ => None,
+    }
+}
+
+impl OptionalTag<'_> {
+    /// Whether the tag can be omitted when `next` is the tag right after it,
+    /// or when nothing follows. Following the HTML spec, this only depends on
+    /// the next tag, so anything else that follows keeps the tag.
+    pub(super) fn omissible_before(&self, next: Option<&Token<'_>>) -> bool {
+        let (start, end) = match next {
+            Some(Token::StartTag(tag)) => (Some(tag), None),
+            Some(Token::EndTag(tag)) => (None, Some(tag)),
+            Some(_) => return false,
+            None => (None, None),
+        };
+        let starts =
+            |names: &[&str]| start.is_some_and(|tag| names.iter().any(|name| tag.is(name)));
+
+        match *self {
+            Self::Start(name) if name.eq_ignore_ascii_case("html") => next.is_some(),
+            Self::Start(name) if name.eq_ignore_ascii_case("head") => {
+                start.is_some() || end.is_some_and(|tag| tag.is("head"))
+            }
+            Self::Start(_) => {
+                start.is_some_and(|tag| !is_any(tag.name, KEEPS_BODY_START))
+                    || end.is_some_and(|tag| tag.is("body"))
+            }
+            Self::End { name, parent } => {
+                let closes_parent = end.zip(parent).is_some_and(|(tag, parent)| tag.is(parent));
+                end_tag_omissible(name, closes_parent, parent, next.is_some(), starts)
+            }
+        }
+    }
+}
+
+fn end_tag_omissible(
+    name: &str,
+    closes_parent: bool,
+    parent: Option<&str>,
+    followed: bool,
+    starts: impl Fn(&[&str]) -> bool,
+) -> bool {
+    match name.to_ascii_lowercase().as_str() {
+        "li" => starts(&["li"]) || closes_parent,
+        "dt" => starts(&["dt", "dd"]),
+        "dd" => starts(&["dt", "dd"]) || closes_parent,
+        "p" => {
+            starts(CLOSES_PARAGRAPH)
+                || (closes_parent
+                    && parent.is_some_and(|parent| !is_any(parent, KEEPS_PARAGRAPH_END)))
+        }
+        "rt" | "rp" => starts(&["rt", "rp"]) || closes_parent,
+        "optgroup" => starts(&["optgroup", "hr"]) || closes_parent,
+        "option" => starts(&["option", "optgroup", "hr"]) || closes_parent,
+        "thead" => starts(&["tbody", "tfoot"]),
+        "tbody" => starts(&["tbody", "tfoot"]) || closes_parent,
+        "tfoot" => closes_parent,
+        "tr" => starts(&["tr"]) || closes_parent,
+        "td" | "th" => starts(&["td", "th"]) || closes_parent,
+        "head" => followed,
+        "body" | "html" => true,
+        _ => false,
+    }
+}
+
+fn is_any(name: &str, names: &[&str]) -> bool {
+    names
+        .iter()
+        .any(|candidate| candidate.eq_ignore_ascii_case(name))
+}
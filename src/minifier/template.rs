@@ -1,30 +1,457 @@
+use super::Options;
+use super::lexer::TagLexer;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Askama tag delimiters: `{{ }}`, `{% %}` and `{# #}` unless a custom
+/// `[[syntax]]` from `askama.toml` is selected.
+#[derive(Clone)]
+pub(crate) struct Syntax {
+    pub(crate) block_start: String,
+    pub(crate) block_end: String,
+    pub(crate) expr_start: String,
+    pub(crate) expr_end: String,
+    pub(crate) comment_start: String,
+    pub(crate) comment_end: String,
+}
+
+impl Default for Syntax {
+    fn default() -> Self {
+        Self {
+            block_start: "{%".to_owned(),
+            block_end: "%}".to_owned(),
+            expr_start: "{{".to_owned(),
+            expr_end: "}}".to_owned(),
+            comment_start: "{#".to_owned(),
+            comment_end: "#}".to_owned(),
+        }
+    }
+}
+
+impl Syntax {
+    /// Finds the tag whose start delimiter `matches`, preferring the longest
+    /// one when several delimiters share a prefix.
+    fn tag_at(&self, mut matches: impl FnMut(&str) -> bool) -> Option<(TagKind, &str, &str)> {
+        [
+            (TagKind::Expr, &self.expr_start, &self.expr_end),
+            (TagKind::Block, &self.block_start, &self.block_end),
+            (TagKind::Comment, &self.comment_start, &self.comment_end),
+        ]
+        .into_iter()
+        .filter(|(_, start, _)| matches(start))
+        .max_by_key(|(_, start, _)| start.len())
+        .map(|(kind, start, end)| (kind, start.as_str(), end.as_str()))
+    }
+
+    fn starts_tag(&self, ch: char) -> bool {
+        [&self.expr_start, &self.block_start, &self.comment_start]
+            .into_iter()
+            .any(|start| start.starts_with(ch))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TagKind {
+    Expr,
+    Block,
+    Comment,
+}
+
+/// Askama's whitespace handling next to a tag: `+`, `-` and `~` markers or
+/// the configured default for tags without one.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum Whitespace {
+    #[default]
+    Preserve,
+    Suppress,
+    Minimize,
+}
+
+impl Whitespace {
+    /// Parses a `whitespace` setting the way Askama does, accepting both the
+    /// names and the marker characters.
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value {
+            "+" | "preserve" => Some(Self::Preserve),
+            "-" | "suppress" => Some(Self::Suppress),
+            "~" | "minimize" => Some(Self::Minimize),
+            _ => None,
+        }
+    }
+
+    pub(super) fn from_marker(marker: char) -> Option<Self> {
+        match marker {
+            '+' => Some(Self::Preserve),
+            '-' => Some(Self::Suppress),
+            '~' => Some(Self::Minimize),
+            _ => None,
+        }
+    }
+
+    fn apply(self, whitespace: &str) -> &str {
+        match self {
+            Self::Preserve => whitespace,
+            Self::Suppress => "",
+            Self::Minimize if whitespace.is_empty() => "",
+            Self::Minimize if whitespace.contains('\n') => "\n",
+            Self::Minimize => " ",
+        }
+    }
+}
+
+pub(crate) struct AskamaTag<'a> {
+    pub(crate) kind: TagKind,
+    /// The whole tag, delimiters included.
+    pub(crate) source: &'a str,
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    pub(crate) inner_start: usize,
+    pub(crate) inner: &'a str,
+}
+
+/// The inside of an Askama tag with its whitespace control markers split off.
+pub(crate) struct TagContent<'a> {
+    pub(crate) open_marker: Option<char>,
+    pub(crate) content: &'a str,
+    pub(crate) close_marker: Option<char>,
+}
+
+pub(crate) struct BlockTag<'a> {
+    pub(crate) open_marker: Option<char>,
+    pub(crate) keyword: &'a str,
+    pub(crate) rest: &'a str,
+    /// Byte offset of `rest` in the scanned source.
+    pub(crate) rest_start: usize,
+    pub(crate) close_marker: Option<char>,
+}
+
+impl<'a> AskamaTag<'a> {
+    pub(crate) fn content(&self) -> TagContent<'a> {
+        let (open_marker, inner) = strip_leading_marker(self.inner);
+        let (close_marker, inner) = strip_trailing_marker(inner);
+
+        TagContent {
+            open_marker,
+            content: inner.trim(),
+            close_marker,
+        }
+    }
+
+    pub(crate) fn block(&self) -> Option<BlockTag<'a>> {
+        if self.kind != TagKind::Block {
+            return None;
+        }
+
+        let (open_marker, unmarked) = strip_leading_marker(self.inner);
+        let marker_len = self.inner.len() - unmarked.len();
+        let (close_marker, unmarked) = strip_trailing_marker(unmarked);
+        let content = unmarked.trim_start();
+        let content_start = marker_len + (unmarked.len() - content.len());
+        let content = content.trim_end();
+        let keyword_len = identifier_len(content);
+        let after_keyword = &content[keyword_len..];
+        let rest = after_keyword.trim_start();
+        let rest_start =
+            self.inner_start + content_start + keyword_len + (after_keyword.len() - rest.len());
+        let rest = rest.trim_end();
+
+        Some(BlockTag {
+            open_marker,
+            keyword: &content[..keyword_len],
+            rest,
+            rest_start,
+            close_marker,
+        })
+    }
+}
+
+/// Iterates over the Askama tags of a template. The contents of a
+/// `{% raw %}` block are plain text, so the next tag after `raw` is always
+/// its `endraw`.
+pub(crate) struct AskamaTags<'a> {
+    source: &'a str,
+    syntax: &'a Syntax,
+    position: usize,
+    in_raw: bool,
+}
+
+pub(crate) fn askama_tags<'a>(source: &'a str, syntax: &'a Syntax) -> AskamaTags<'a> {
+    AskamaTags {
+        source,
+        syntax,
+        position: 0,
+        in_raw: false,
+    }
+}
+
+impl<'a> Iterator for AskamaTags<'a> {
+    type Item = AskamaTag<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.in_raw {
+            let block_start = self.syntax.block_start.as_str();
+            self.position = self.source[self.position..]
+                .match_indices(block_start)
+                .map(|(offset, _)| self.position + offset)
+                .find(|&start| starts_endraw(self.source[start..].chars(), self.syntax))?;
+            self.in_raw = false;
+        }
+
+        loop {
+            let start = self.position
+                + self.source[self.position..].find(|ch| self.syntax.starts_tag(ch))?;
+            let rest = &self.source[start..];
+            let Some((kind, open, end)) = self.syntax.tag_at(|open| rest.starts_with(open)) else {
+                self.position = start + rest.chars().next().map_or(1, char::len_utf8);
+                continue;
+            };
+
+            let inner_start = start + open.len();
+            let mut lexer = TagLexer::new(self.syntax, kind, end);
+            let (inner_end, tag_end) = match self.source[inner_start..]
+                .char_indices()
+                .find(|&(_, ch)| lexer.push(ch))
+            {
+                Some((offset, ch)) => {
+                    let tag_end = inner_start + offset + ch.len_utf8();
+                    (tag_end - end.len(), tag_end)
+                }
+                None => (self.source.len(), self.source.len()),
+            };
+            self.position = tag_end;
+
+            let tag = AskamaTag {
+                kind,
+                source: &self.source[start..tag_end],
+                start,
+                end: tag_end,
+                inner_start,
+                inner: &self.source[inner_start..inner_end],
+            };
+            self.in_raw = tag.block().is_some_and(|block| block.keyword == "raw");
+            return Some(tag);
+        }
+    }
+}
+
+/// Parses a plain Askama string literal such as the path of `{% include "..." %}`.
+pub(crate) fn parse_string_literal(value: &str) -> Option<String> {
+    let body = value.strip_prefix('"')?.strip_suffix('"')?;
+    let mut result = String::with_capacity(body.len());
+    let mut chars = body.chars();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => result.push(chars.next()?),
+            '"' => return None,
+            _ => result.push(ch),
+        }
+    }
+
+    Some(result)
+}
+
+/// Returns the length of the identifier at the start of `value`.
+pub(crate) fn identifier_len(value: &str) -> usize {
+    value
+        .find(|ch: char| !(ch.is_alphanumeric() || ch == '_'))
+        .unwrap_or(value.len())
+}
+
+fn block_keyword(inner: &str) -> &str {
+    let inner = strip_leading_marker(inner).1.trim_start();
+    &inner[..identifier_len(inner)]
+}
+
+/// Whether `chars` start with `{% endraw %}`, which Askama finds textually
+/// with optional whitespace control markers.
+fn starts_endraw(chars: impl Iterator<Item = char>, syntax: &Syntax) -> bool {
+    let mut chars = chars.peekable();
+    if !syntax
+        .block_start
+        .chars()
+        .all(|expected| chars.next() == Some(expected))
+    {
+        return false;
+    }
+
+    chars.next_if(|ch| matches!(ch, '-' | '+' | '~'));
+    while chars.next_if(char::is_ascii_whitespace).is_some() {}
+    if !"endraw"
+        .chars()
+        .all(|expected| chars.next() == Some(expected))
+    {
+        return false;
+    }
+
+    while chars.next_if(char::is_ascii_whitespace).is_some() {}
+    chars.next_if(|ch| matches!(ch, '-' | '+' | '~'));
+    syntax
+        .block_end
+        .chars()
+        .all(|expected| chars.next() == Some(expected))
+}
+
+fn strip_leading_marker(value: &str) -> (Option<char>, &str) {
+    match value.chars().next() {
+        Some(marker @ ('-' | '+' | '~')) => (Some(marker), &value[1..]),
+        _ => (None, value),
+    }
+}
+
+fn strip_trailing_marker(value: &str) -> (Option<char>, &str) {
+    match value.chars().next_back() {
+        Some(marker @ ('-' | '+' | '~')) => (Some(marker), &value[..value.len() - 1]),
+        _ => (None, value),
+    }
+}
+
+/// Applies Askama's whitespace control to the text around every tag, so
+/// whitespace that `-` or `~` would remove or shrink is not emitted at all.
+///
+/// Like Askama, whitespace-only text between two tags is governed by the
+/// following tag unless the preceding one suppresses it, and trailing text
+/// of the template follows the configured default.
+pub(super) fn apply_whitespace_control(source: &str, options: &Options) -> String {
+    let mut result = String::with_capacity(source.len());
+    let mut copied = 0;
+    let mut after = Whitespace::Preserve;
+
+    for tag in askama_tags(source, &options.syntax) {
+        let content = tag.content();
+        push_controlled_text(
+            &mut result,
+            &source[copied..tag.start],
+            after,
+            options.whitespace_for(content.open_marker),
+        );
+        result.push_str(&source[tag.start..tag.end]);
+        after = options.whitespace_for(content.close_marker);
+        copied = tag.end;
+    }
+
+    push_controlled_text(&mut result, &source[copied..], after, options.whitespace);
+    result
+}
+
+fn push_controlled_text(result: &mut String, text: &str, after: Whitespace, before: Whitespace) {
+    let trimmed = text.trim_ascii_start();
+    if trimmed.is_empty() {
+        if after != Whitespace::Suppress {
+            result.push_str(before.apply(text));
+        }
+        return;
+    }
+
+    let value = trimmed.trim_ascii_end();
+    result.push_str(after.apply(&text[..text.len() - trimmed.len()]));
+    result.push_str(value);
+    result.push_str(before.apply(&trimmed[value.len()..]));
+}
+
+/// Copies a whitespace run verbatim when it ends at a tag whose opening
+/// marker keeps whitespace, instead of letting the caller collapse it.
+pub(super) fn try_push_kept_whitespace(
+    ch: char,
+    chars: &mut Peekable<Chars<'_>>,
+    target: &mut String,
+    options: &Options,
+    in_raw: bool,
+) -> bool {
+    if !ch.is_ascii_whitespace() {
+        return false;
+    }
+
+    let mut lookahead = chars.clone();
+    let mut run_len = 0;
+    while lookahead.next_if(char::is_ascii_whitespace).is_some() {
+        run_len += 1;
+    }
+
+    let Some(next) = lookahead.next() else {
+        return false;
+    };
+    if in_raw
+        && !starts_endraw(
+            std::iter::once(next).chain(lookahead.clone()),
+            &options.syntax,
+        )
+    {
+        return false;
+    }
+    let Some((_, start, _)) = options
+        .syntax
+        .tag_at(|start| starts_with_chars(next, &lookahead, start))
+    else {
+        return false;
+    };
+    let marker = lookahead.nth(start.chars().count() - 1);
+    if !options.keeps_whitespace(marker) {
+        return false;
+    }
+
+    target.push(ch);
+    target.extend(chars.take(run_len));
+    true
+}
+
+/// Copies an Askama tag verbatim. Whitespace after a tag whose closing
+/// marker keeps it is copied verbatim as well.
+///
+/// `in_raw` tracks `{% raw %}` blocks: inside one, only `{% endraw %}` is a
+/// tag and everything else is left to the caller as plain text.
 pub(super) fn try_push_askama_template(
     ch: char,
-    chars: &mut std::iter::Peekable<std::str::Chars<'_>>,
+    chars: &mut Peekable<Chars<'_>>,
     target: &mut String,
+    options: &Options,
+    in_raw: &mut bool,
 ) -> Option<char> {
-    if ch != '{' {
+    if *in_raw && !starts_endraw(std::iter::once(ch).chain(chars.clone()), &options.syntax) {
         return None;
     }
 
-    let &next_ch = chars.peek()?;
-
-    let end = match next_ch {
-        '{' => "}}",
-        '%' => "%}",
-        '#' => "#}",
-        _ => return None,
-    };
+    let (kind, start, end) = options
+        .syntax
+        .tag_at(|start| starts_with_chars(ch, chars, start))?;
 
     target.push(ch);
+    for _ in 1..start.chars().count() {
+        target.extend(chars.next());
+    }
+
+    let mut lexer = TagLexer::new(&options.syntax, kind, end);
     let mut last_ch = ch;
     for next_ch in chars.by_ref() {
         target.push(next_ch);
         last_ch = next_ch;
-        if target.ends_with(end) {
+        if lexer.push(next_ch) {
             break;
         }
     }
 
+    let inner = lexer.inner().strip_suffix(end);
+    if kind == TagKind::Block {
+        let keyword = inner.map(block_keyword);
+        *in_raw = keyword == Some("raw");
+    }
+
+    let close_marker = inner.and_then(|inner| inner.chars().next_back());
+    if options.keeps_whitespace(close_marker) {
+        while let Some(next_ch) = chars.next_if(char::is_ascii_whitespace) {
+            target.push(next_ch);
+        }
+    }
+
     Some(last_ch)
 }
+
+fn starts_with_chars(ch: char, chars: &Peekable<Chars<'_>>, value: &str) -> bool {
+    let mut expected = value.chars();
+    if expected.next() != Some(ch) {
+        return false;
+    }
+
+    let mut lookahead = chars.clone();
+    expected.all(|expected| lookahead.next() == Some(expected))
+}
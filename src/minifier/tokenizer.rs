@@ -0,0 +1,334 @@
+use super::template::{AskamaTag, AskamaTags, Syntax, askama_tags};
+use std::iter::Peekable;
+
+/// A piece of an HTML template. Askama tags are tokens of their own wherever
+/// they appear, and markup is never searched for inside them.
+pub(super) enum Token<'a> {
+    /// Text between markup, whitespace included.
+    Text(&'a str),
+    StartTag(Tag<'a>),
+    EndTag(Tag<'a>),
+    /// `<!DOCTYPE ...>` and other `<!...>` or `<?...>` markup.
+    Declaration(Tag<'a>),
+    /// An HTML comment. `terminated` is unset when it runs to the end of the
+    /// template.
+    Comment {
+        source: &'a str,
+        terminated: bool,
+    },
+    Askama(AskamaTag<'a>),
+    /// The content of a `<script>` or `<style>` element, read up to its end
+    /// tag as a whole. `closed` is unset when there is no end tag.
+    RawText {
+        element: &'static str,
+        content: &'a str,
+        closed: bool,
+    },
+}
+
+/// A start tag, end tag or declaration.
+pub(super) struct Tag<'a> {
+    /// `<`, `</`, `<!` or `<?`.
+    pub(super) open: &'a str,
+    /// The element name as written, empty when Askama generates it.
+    pub(super) name: &'a str,
+    /// Everything between the name and the closing `>`.
+    pub(super) parts: Vec<TagPart<'a>>,
+    /// Unset when the tag runs to the end of the template.
+    pub(super) closed: bool,
+}
+
+pub(super) enum TagPart<'a> {
+    Whitespace(&'a str),
+    /// A quoted attribute value, quotes included.
+    Quoted(&'a str),
+    /// Any other run of characters, such as an attribute name and its `=`.
+    Text(&'a str),
+    Askama(AskamaTag<'a>),
+}
+
+impl Tag<'_> {
+    pub(super) fn is(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+    }
+
+    /// Whether the tag ends with `/>`.
+    pub(super) fn self_closing(&self) -> bool {
+        let last = self
+            .parts
+            .iter()
+            .rev()
+            .find(|part| !matches!(part, TagPart::Whitespace(_)));
+        matches!(last, Some(TagPart::Text(text)) if text.ends_with('/'))
+    }
+}
+
+/// Elements whose content is read as one `RawText` token.
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style"];
+
+/// Elements whose content is text and Askama tags, without markup.
+const ESCAPABLE_RAW_TEXT_ELEMENTS: &[&str] = &["textarea", "title"];
+
+/// Elements that never have content or an end tag.
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// Splits an HTML template into tokens, each with its byte offset.
+pub(super) struct Tokenizer<'a> {
+    source: &'a str,
+    tags: Peekable<AskamaTags<'a>>,
+    position: usize,
+    /// Set after the start tag of an element whose content is not markup.
+    text_element: Option<TextElement>,
+}
+
+#[derive(Clone, Copy)]
+enum TextElement {
+    Raw(&'static str),
+    Escapable(&'static str),
+}
+
+pub(super) fn tokenize<'a>(source: &'a str, syntax: &'a Syntax) -> Tokenizer<'a> {
+    Tokenizer {
+        source,
+        tags: askama_tags(source, syntax).peekable(),
+        position: 0,
+        text_element: None,
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = (usize, Token<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.position;
+        if let Some(TextElement::Raw(element)) = self.text_element {
+            self.text_element = None;
+            let end = self.find(start, |rest| closes_element(rest, element));
+            self.position = end.unwrap_or(self.source.len());
+            let token = Token::RawText {
+                element,
+                content: &self.source[start..self.position],
+                closed: end.is_some(),
+            };
+            return Some((start, token));
+        }
+
+        let rest = self.source.get(start..).filter(|rest| !rest.is_empty())?;
+        let tag_start = self.next_tag_start(start);
+        if tag_start == start {
+            let tag = self.tags.next()?;
+            self.position = tag.end;
+            return Some((start, Token::Askama(tag)));
+        }
+
+        if !self.starts_markup(start, tag_start) {
+            let end = rest[..tag_start - start]
+                .char_indices()
+                .skip(1)
+                .map(|(offset, _)| start + offset)
+                .find(|&at| self.starts_markup(at, tag_start))
+                .unwrap_or(tag_start);
+            self.position = end;
+            return Some((start, Token::Text(&self.source[start..end])));
+        }
+
+        let token = if rest.starts_with("<!--") {
+            let end = self.find(start + 4, |rest| rest.starts_with("-->"));
+            self.position = end.map_or(self.source.len(), |end| end + 3);
+            Token::Comment {
+                source: &self.source[start..self.position],
+                terminated: end.is_some(),
+            }
+        } else if rest.starts_with("</") {
+            self.text_element = None;
+            Token::EndTag(self.read_tag(start, 2))
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            Token::Declaration(self.read_tag(start, 2))
+        } else {
+            let tag = self.read_tag(start, 1);
+            self.text_element = text_element(tag.name);
+            Token::StartTag(tag)
+        };
+        Some((start, token))
+    }
+}
+
+impl<'a> Tokenizer<'a> {
+    /// Moves past the Askama tags that start before `position` and returns
+    /// the start of the next one.
+    fn next_tag_start(&mut self, position: usize) -> usize {
+        while self.tags.next_if(|tag| tag.start < position).is_some() {}
+        self.tags.peek().map_or(self.source.len(), |tag| tag.start)
+    }
+
+    /// Finds the first position from `from`, outside Askama tags, where
+    /// `matches` holds for the rest of the source.
+    fn find(&mut self, from: usize, matches: impl Fn(&str) -> bool) -> Option<usize> {
+        let mut position = from;
+        loop {
+            let tag_start = self.next_tag_start(position);
+            let found = self.source[position..tag_start]
+                .char_indices()
+                .map(|(offset, _)| position + offset)
+                .find(|&at| matches(&self.source[at..]));
+            if found.is_some() {
+                return found;
+            }
+            position = self.tags.next()?.end;
+        }
+    }
+
+    /// Whether markup starts at `at`, where `tag_start` is the start of the
+    /// next Askama tag. Inside `<textarea>` and `<title>` only their end tag
+    /// is markup, and elsewhere a `<` that is not followed by a name, `/`,
+    /// `!` or `?` is text.
+    fn starts_markup(&self, at: usize, tag_start: usize) -> bool {
+        let rest = &self.source[at..];
+        if let Some(TextElement::Escapable(element)) = self.text_element {
+            return closes_element(rest, element);
+        }
+
+        let Some(after) = rest.strip_prefix('<') else {
+            return false;
+        };
+        match after.chars().next() {
+            Some('!' | '?') => true,
+            Some('/') => {
+                after[1..].starts_with(|ch: char| ch.is_ascii_alphabetic()) || at + 2 == tag_start
+            }
+            Some(ch) => ch.is_ascii_alphabetic() || at + 1 == tag_start,
+            None => false,
+        }
+    }
+
+    /// Reads the tag at `start` whose name follows `open_len` bytes of
+    /// `<`, `</`, `<!` or `<?`.
+    fn read_tag(&mut self, start: usize, open_len: usize) -> Tag<'a> {
+        let name_start = start + open_len;
+        let tag_start = self.next_tag_start(name_start);
+        let name_end = self.source[name_start..tag_start]
+            .find(|ch: char| ch.is_whitespace() || matches!(ch, '/' | '>'))
+            .map_or(tag_start, |offset| name_start + offset);
+
+        let mut parts = Vec::new();
+        let mut position = name_end;
+        let closed = loop {
+            let Some(ch) = self.source[position..].chars().next() else {
+                break false;
+            };
+            let tag_start = self.next_tag_start(position);
+            if tag_start == position
+                && let Some(tag) = self.tags.next()
+            {
+                position = tag.end;
+                parts.push(TagPart::Askama(tag));
+                continue;
+            }
+            if ch == '>' {
+                position += 1;
+                break true;
+            }
+
+            let rest = &self.source[position..tag_start];
+            let (end, part): (usize, fn(&'a str) -> TagPart<'a>) = if ch.is_whitespace() {
+                let len = rest
+                    .find(|ch: char| !ch.is_whitespace())
+                    .unwrap_or(rest.len());
+                (position + len, TagPart::Whitespace)
+            } else if ch == '"' || ch == '\'' {
+                let end = self.find(position + 1, |rest| rest.starts_with(ch));
+                (
+                    end.map_or(self.source.len(), |end| end + 1),
+                    TagPart::Quoted,
+                )
+            } else {
+                let len = rest
+                    .find(|ch: char| ch.is_whitespace() || matches!(ch, '"' | '\'' | '>'))
+                    .unwrap_or(rest.len());
+                (position + len, TagPart::Text)
+            };
+            parts.push(part(&self.source[position..end]));
+            position = end;
+        };
+        self.position = position;
+
+        Tag {
+            open: &self.source[start..name_start],
+            name: &self.source[name_start..name_end],
+            parts,
+            closed,
+        }
+    }
+}
+
+fn text_element(name: &str) -> Option<TextElement> {
+    let find = |elements: &[&'static str]| {
+        elements
+            .iter()
+            .copied()
+            .find(|element| element.eq_ignore_ascii_case(name))
+    };
+    find(RAW_TEXT_ELEMENTS)
+        .map(TextElement::Raw)
+        .or_else(|| find(ESCAPABLE_RAW_TEXT_ELEMENTS).map(TextElement::Escapable))
+}
+
+/// Whether `rest` starts with the end tag of `element`.
+fn closes_element(rest: &str, element: &str) -> bool {
+    rest.strip_prefix("</")
+        .and_then(|rest| {
+            rest.get(..element.len())
+                .map(|name| (name, &rest[element.len()..]))
+        })
+        .is_some_and(|(name, after)| {
+            name.eq_ignore_ascii_case(element)
+                && after.starts_with(|ch: char| ch.is_whitespace() || ch == '>')
+        })
+}
+
+/// The elements opened by start tags and not closed yet.
+#[derive(Default)]
+pub(super) struct OpenElements<'a> {
+    names: Vec<&'a str>,
+}
+
+impl<'a> OpenElements<'a> {
+    /// Follows a token. Start tags open their element unless it is void or
+    /// the tag is self-closing, and end tags close the innermost open element
+    /// with their name along with every element opened after it. Like in
+    /// browsers, end tags without an open element are ignored.
+    pub(super) fn update(&mut self, token: &Token<'a>) {
+        match token {
+            Token::StartTag(tag)
+                if !tag.name.is_empty()
+                    && !tag.self_closing()
+                    && !VOID_ELEMENTS.iter().any(|void| tag.is(void)) =>
+            {
+                self.names.push(tag.name);
+            }
+            Token::EndTag(tag) => {
+                if let Some(index) = self.names.iter().rposition(|name| tag.is(name)) {
+                    self.names.truncate(index);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the parent of the innermost open element that the end tag
+    /// `tag` closes, which is `None` at the top level of the template, or
+    /// `None` when no such element is open.
+    pub(super) fn parent_of(&self, tag: &Tag<'_>) -> Option<Option<&'a str>> {
+        let index = self.names.iter().rposition(|name| tag.is(name))?;
+        Some(index.checked_sub(1).map(|parent| self.names[parent]))
+    }
+
+    pub(super) fn contains(&self, name: &str) -> bool {
+        self.names
+            .iter()
+            .any(|open| open.eq_ignore_ascii_case(name))
+    }
+}
@@ -9,3 +9,9 @@ pub(super) fn trim_trailing_whitespace(value: &mut String) {
         value.pop();
     }
 }
+
+/// Ends `value` with a line break in place of a collapsed trailing space.
+pub(super) fn push_line_break(value: &mut String) {
+    trim_trailing_space(value);
+    value.push('\n');
+}
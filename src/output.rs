@@ -0,0 +1,62 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// A minified template as named in the dump and report directories. Items
+/// are told apart by the Rust file that declares them, so items of the same
+/// name in different modules do not overwrite each other.
+pub(crate) struct TemplateKey<'a> {
+    /// The Rust file of the item, as passed to the compiler.
+    pub(crate) rust_file: &'a str,
+    /// The item, with `::Variant` for enum variants and `#2`, `#3`, ... for
+    /// further templates of the same item.
+    pub(crate) name: &'a str,
+}
+
+impl TemplateKey<'_> {
+    /// `<rust file>::<name>`, which identifies the template in its crate.
+    pub(crate) fn id(&self) -> String {
+        format!("{}::{}", self.rust_file, self.name)
+    }
+
+    /// The path of the template's file in a crate directory: the Rust file
+    /// without its extension, then the name with `.` for `::`.
+    pub(crate) fn file(&self, ext: &str) -> String {
+        let mut file = String::new();
+        for component in Path::new(self.rust_file).with_extension("").components() {
+            if let Component::Normal(part) = component {
+                file.push_str(&part.to_string_lossy());
+                file.push('/');
+            }
+        }
+        file.push_str(&self.name.replace("::", "."));
+        file.push('.');
+        file.push_str(ext);
+        file
+    }
+}
+
+/// The directory of the current crate in the directory named by the
+/// environment variable `var`, or `None` when it is unset or empty.
+pub(crate) fn crate_dir(var: &str) -> Option<PathBuf> {
+    let root = std::env::var_os(var).filter(|root| !root.is_empty())?;
+    Some(match std::env::var_os("CARGO_CRATE_NAME") {
+        Some(krate) => Path::new(&root).join(krate),
+        None => PathBuf::from(root),
+    })
+}
+
+/// Writes `content` to `path`, creating its directory. The content is
+/// written to a temporary file that is then renamed over `path`, so
+/// compilers writing the same file at once, such as for a library and its
+/// tests, never leave it half-written.
+pub(crate) fn write_file(path: &Path, content: &str) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|error| format!("failed to create `{}`: {error}", dir.display()))?;
+    }
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = path.with_file_name(format!(".{file_name}.{}.tmp", std::process::id()));
+    fs::write(&temporary, content)
+        .and_then(|()| fs::rename(&temporary, path))
+        .map_err(|error| format!("failed to write `{}`: {error}", path.display()))
+}
@@ -0,0 +1,70 @@
+use crate::minifier::Stats;
+use crate::output::{TemplateKey, crate_dir, write_file};
+use serde_derive::Serialize;
+
+/// Directory that receives the JSON minification report of each template
+/// when set.
+pub(crate) const REPORT_DIR_VAR: &str = "ASKAMA_MINIFY_REPORT_DIR";
+
+/// A minified template as written to the report.
+#[derive(Serialize)]
+struct ReportEntry {
+    rust_file: String,
+    /// The item, with `::Variant` for enum variants.
+    item: String,
+    source: String,
+    ext: String,
+    original_bytes: usize,
+    minified_bytes: usize,
+    saved: SavedBytes,
+    /// `conservative`, `lightningcss`, `mixed`, or `null` without styles.
+    css_backend: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SavedBytes {
+    html: usize,
+    css: usize,
+    js: usize,
+    comments: usize,
+}
+
+pub(crate) struct ReportedTemplate<'a> {
+    pub(crate) key: &'a TemplateKey<'a>,
+    /// The `path` of the template, or how inline templates are shown.
+    pub(crate) source: &'a str,
+    pub(crate) ext: &'a str,
+    pub(crate) original_len: usize,
+    pub(crate) minified_len: usize,
+    pub(crate) stats: &'a Stats,
+}
+
+/// Writes the report of a minified template as JSON into the report
+/// directory when `ASKAMA_MINIFY_REPORT_DIR` is set. Each template gets its
+/// own file, named like its dump, so expansions never share a file and a
+/// later step can aggregate them.
+pub(crate) fn report_template(template: &ReportedTemplate<'_>) -> Result<(), String> {
+    let Some(dir) = crate_dir(REPORT_DIR_VAR) else {
+        return Ok(());
+    };
+    let stats = template.stats;
+    let saved = template.original_len.saturating_sub(template.minified_len);
+    let entry = ReportEntry {
+        rust_file: template.key.rust_file.to_owned(),
+        item: template.key.name.to_owned(),
+        source: template.source.to_owned(),
+        ext: template.ext.to_owned(),
+        original_bytes: template.original_len,
+        minified_bytes: template.minified_len,
+        saved: SavedBytes {
+            html: saved.saturating_sub(stats.css_saved + stats.js_saved + stats.comments_saved),
+            css: stats.css_saved,
+            js: stats.js_saved,
+            comments: stats.comments_saved,
+        },
+        css_backend: stats.css_backend.map(|backend| backend.name().to_owned()),
+    };
+    let content = serde_json::to_string_pretty(&entry)
+        .map_err(|error| format!("failed to write the minification report: {error}"))?;
+    write_file(&dir.join(template.key.file("json")), &content)
+}
@@ -0,0 +1,24 @@
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Builds the templates of `tests/output` with trybuild while the
+/// environment variable `var` names a new directory in the test's temporary
+/// directory, and returns it. Each caller must be the only test of its
+/// binary, since the variable is set for the whole process.
+pub fn build_with_dir(var: &str) -> PathBuf {
+    // A new directory changes the tracked variable, so the fixture is
+    // expanded again on every run.
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let name = var.to_ascii_lowercase();
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{name}-{nanos}"));
+    // SAFETY: the caller is the only test of its binary, so nothing else
+    // reads the environment at the same time.
+    unsafe { std::env::set_var(var, &dir) };
+    let tests = trybuild::TestCases::new();
+    tests.pass("tests/output/templates.rs");
+    drop(tests);
+    dir
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use serde_json::{Value, json};
+use std::fs;
+
+#[test]
+fn dumps_minified_templates() {
+    let root = common::build_with_dir("ASKAMA_MINIFY_DUMP_DIR");
+
+    let crates: Vec<_> = fs::read_dir(&root).unwrap().collect();
+    assert_eq!(crates.len(), 1);
+    let dir = crates.into_iter().next().unwrap().unwrap().path();
+    let manifest: Value =
+        basic_toml::from_str(&fs::read_to_string(dir.join("manifest.toml")).unwrap()).unwrap();
+    let manifest = manifest.as_object().unwrap();
+    let entry = |suffix: &str| {
+        let (_, entry) = manifest
+            .iter()
+            .find(|(id, _)| id.ends_with(suffix))
+            .unwrap_or_else(|| panic!("no `{suffix}` in {manifest:?}"));
+        let minified = fs::read_to_string(dir.join(entry["file"].as_str().unwrap())).unwrap();
+        (entry.clone(), minified)
+    };
+
+    assert_eq!(manifest.len(), 3);
+    let (page, minified) = entry("tests/output/templates.rs::Page");
+    assert_eq!(minified, "<style>p{color:red}</style> <p> {{ title }} </p>");
+    assert_eq!(
+        page,
+        json!({
+            "source": "<source attribute>",
+            "file": page["file"],
+            "original_len": 75,
+            "minified_len": 48,
+        })
+    );
+    assert!(
+        page["file"]
+            .as_str()
+            .unwrap()
+            .ends_with("tests/output/templates/Page.html")
+    );
+    let (other, minified) = entry("tests/output/page.rs::Page");
+    assert_eq!(minified, "<div> other </div>");
+    assert!(
+        other["file"]
+            .as_str()
+            .unwrap()
+            .ends_with("tests/output/page/Page.html")
+    );
+    let (_, minified) = entry("tests/output/templates.rs::Pages::Home");
+    assert_eq!(minified, "<main> home </main>");
+
+    fs::remove_dir_all(root).unwrap();
+}
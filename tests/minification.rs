@@ -49,6 +49,24 @@ struct PreTemplate;
 #[derive(Template)]
 struct TextareaTemplate;
 
+#[template_minify(
+    source = "<pre>  outer  <pre>  inner  </pre>  still pre  </pre>  <p>  after  </p>",
+    ext = "html"
+)]
+#[derive(Template)]
+struct NestedPreTemplate;
+
+#[template_minify(
+    source = "<textarea>  <pre>  a  </textarea>  <p>  after  </p>",
+    ext = "html"
+)]
+#[derive(Template)]
+struct PreInsideTextareaTemplate;
+
+#[template_minify(source = "<p>  a < b's   value  </p>  <p>  next  </p>", ext = "html")]
+#[derive(Template)]
+struct StrayLessThanTemplate;
+
 #[template_minify(
     source = "<style>/* removed */ body { margin: 0; color: red; }</style>",
     ext = "html"
@@ -113,6 +131,270 @@ struct ScriptLineTerminatorTemplate;
 #[derive(Template)]
 struct ScriptCommentLineTerminatorTemplate;
 
+#[template_minify(
+    source = "<ul>\n  {%- for item in items -%}\n    <li>{{ item }}</li>\n  {%- endfor -%}\n</ul>",
+    ext = "html"
+)]
+#[derive(Template)]
+struct SuppressWhitespaceTemplate<'a> {
+    items: &'a [&'a str],
+}
+
+#[template_minify(source = "<p>{{- 2 -}}<!-- c -->\n{{ 1 }}</p>", ext = "html")]
+#[derive(Template)]
+struct CommentAfterDashMarkerTemplate;
+
+#[template_minify(source = "<p>{{ 2 }}\n<!-- c -->{{- 1 }}</p>", ext = "html")]
+#[derive(Template)]
+struct CommentBeforeDashMarkerTemplate;
+
+#[template_minify(source = "<p>a   {{+ value +}}   b</p>", ext = "html")]
+#[derive(Template)]
+struct PreserveWhitespaceTemplate<'a> {
+    value: &'a str,
+}
+
+#[template_minify(source = "<p>a \n  {{~ value ~}}   b</p>", ext = "html")]
+#[derive(Template)]
+struct MinimizeWhitespaceTemplate<'a> {
+    value: &'a str,
+}
+
+#[template_minify(
+    source = "<script>function value() { return {{+ value }}; }</script>",
+    ext = "html"
+)]
+#[derive(Template)]
+struct ScriptPreserveWhitespaceTemplate {
+    value: i32,
+}
+
+#[template_minify(
+    source = "<style>.box { margin:\n  {{~ size }}; }</style>",
+    ext = "html"
+)]
+#[derive(Template)]
+struct StyleMinimizeWhitespaceTemplate<'a> {
+    size: &'a str,
+}
+
+#[template_minify(
+    source = "<p>\n  {{ value }}\n</p>",
+    ext = "html",
+    whitespace = "minimize"
+)]
+#[derive(Template)]
+struct DefaultMinimizeWhitespaceTemplate<'a> {
+    value: &'a str,
+}
+
+#[template_minify(
+    source = "<p>\n  {{ value }}\n</p>",
+    ext = "html",
+    whitespace = "suppress"
+)]
+#[derive(Template)]
+struct DefaultSuppressWhitespaceTemplate<'a> {
+    value: &'a str,
+}
+
+#[template_minify(source = r#"<p>{{ "}}   x" }}</p>"#, ext = "html")]
+#[derive(Template)]
+struct ExprDelimiterStringTemplate;
+
+#[template_minify(
+    source = r#"<p>{% if value == "%}   %}" %}yes{% endif %}</p>"#,
+    ext = "html"
+)]
+#[derive(Template)]
+struct BlockDelimiterStringTemplate<'a> {
+    value: &'a str,
+}
+
+#[template_minify(
+    source = r#"<p>{% if value == '"' %}a   b{% endif %}</p>"#,
+    ext = "html"
+)]
+#[derive(Template)]
+struct CharLiteralTemplate {
+    value: char,
+}
+
+#[template_minify(
+    source = r#"<script>{# a {# b #} " #}const value = "a   b";</script>"#,
+    ext = "html"
+)]
+#[derive(Template)]
+struct NestedCommentTemplate;
+
+#[template_minify(
+    source = r#"{% raw %}<div id="app">   {{- message }}   </div>{% endraw %}<p>   {{ value }}</p>"#,
+    ext = "html"
+)]
+#[derive(Template)]
+struct RawBlockTemplate<'a> {
+    value: &'a str,
+}
+
+#[template_minify(
+    source = r#"{% raw %}<p>{{ "   }}</p>{% endraw %}<p>   {{ value }}</p>"#,
+    ext = "html"
+)]
+#[derive(Template)]
+struct RawUnbalancedTemplate<'a> {
+    value: &'a str,
+}
+
+#[template_minify(
+    source = "{% raw %}<script>// removed\nconst  text = `{{ a }}`;</script><style>.a { color: {{ b }}; }</style>{% endraw %}",
+    ext = "html"
+)]
+#[derive(Template)]
+struct RawScriptStyleTemplate;
+
+#[template_minify(
+    source = "<ul>\n  {% for item in items %}\n    {% if !item.is_empty() %}\n    <li>{{ item }}</li>\n    {% endif %}\n  {% endfor %}\n</ul>",
+    ext = "html",
+    strip_block_whitespace = true
+)]
+#[derive(Template)]
+struct StripBlockWhitespaceTemplate<'a> {
+    items: &'a [&'a str],
+}
+
+#[template_minify(
+    source = "<p>\n  <b>a</b>\n  {% if show %}\n  <i>b</i>\n  {% endif %}\n</p>\n<div>\n  {%+ if show %}\n  <p>c</p>\n  {% endif %}\n</div>",
+    ext = "html",
+    strip_block_whitespace = true
+)]
+#[derive(Template)]
+struct StripBlockWhitespaceInlineTemplate {
+    show: bool,
+}
+
+#[template_minify(
+    source = "<div>\n  <p>\n    a <b> b </b>\n  </p>\n  <!-- c -->\n  <ul>\n    {% for item in items %}\n    <li> {{ item }} </li>\n    {% endfor %}\n  </ul>\n</div>",
+    ext = "html",
+    remove_block_whitespace = true
+)]
+#[derive(Template)]
+struct RemoveBlockWhitespaceTemplate<'a> {
+    items: &'a [&'a str],
+}
+
+#[template_minify(
+    source = "<app-card>\n  <span>a</span>\n</app-card>\n<ul>\n  <li>x</li>\n  <li>y</li>\n</ul>",
+    ext = "html",
+    config = "tests/config/elements.toml",
+    remove_block_whitespace = true
+)]
+#[derive(Template)]
+struct ConfiguredElementsTemplate;
+
+#[template_minify(
+    source = "<!DOCTYPE html>\n<html>\n<head>\n  <title>{{ title }}</title>\n</head>\n<body>\n  <p>a</p>\n  <p>b</p>\n  <table>\n    <tr><td>1</td><td>2</td></tr>\n  </table>\n  <a href=\"/\"><p>c</p></a>\n</body>\n</html>\n",
+    ext = "html",
+    remove_block_whitespace = true,
+    omit_optional_tags = true
+)]
+#[derive(Template)]
+struct OptionalTagsTemplate<'a> {
+    title: &'a str,
+}
+
+#[template_minify(
+    source = "<ul>\n  {% for item in items %}\n  <li>{{ item }}</li>\n  {% endfor %}\n</ul>\n<ul> <li>a</li> <li>b</li> </ul>",
+    ext = "html",
+    remove_block_whitespace = true,
+    omit_optional_tags = true
+)]
+#[derive(Template)]
+struct OptionalTagsBeforeAskamaTemplate<'a> {
+    items: &'a [&'a str],
+}
+
+#[template_minify(source = "<div>a </div>", ext = "html", omit_optional_tags = true)]
+#[derive(Template)]
+struct OptionalTagsTrailingSpaceTemplate;
+
+#[template_minify(
+    source = "<p>a</p>  <pre>é </pre>",
+    ext = "html",
+    omit_optional_tags = true
+)]
+#[derive(Template)]
+struct OptionalTagsBeforePreTemplate;
+
+#[template_minify(
+    source = "<a href=\"/home\" class=\"nav link\" title='Say \"hi\"' data-x=\"it's &quot;ok&quot;\" id=\"{{ id }}\" data-y=\"\">x</a>\n<br class=\"x\"/><input value=\"a\" {{- attrs }}><img alt=\"b\">",
+    ext = "html",
+    remove_attribute_quotes = true
+)]
+#[derive(Template)]
+struct AttributeQuotesTemplate<'a> {
+    id: u32,
+    attrs: &'a str,
+}
+
+#[template_minify(
+    source = "<form method=\"GET\">\n<input type=\"text\" disabled=\"disabled\" required=\"\">\n<input type=\"checkbox\" checked=\"\" {{ attrs }}>\n<input type=\"text\"/>\n<button disabled=\"{{ flag }}\" autofocus>x</button>\n<app-toggle disabled=\"disabled\"></app-toggle>\n<script type=\"text/javascript\">go()</script>\n<style type=\"text/css\">p{color:red}</style>\n<input type=\"text\" {{- attrs }}>\n</form>",
+    ext = "html",
+    collapse_boolean_attributes = true,
+    remove_redundant_attributes = true
+)]
+#[derive(Template)]
+struct SimplifiedAttributesTemplate<'a> {
+    attrs: &'a str,
+    flag: bool,
+}
+
+#[template_minify(
+    source = "<pre>\n<div>a</div>\n{% for item in items %}\n<div>{{ item }}</div>\n{% endfor %}\n</pre>",
+    ext = "html",
+    strip_block_whitespace = true
+)]
+#[derive(Template)]
+struct StripBlockWhitespaceInPreTemplate<'a> {
+    items: &'a [&'a str],
+}
+
+#[template_minify(
+    source = "<form method=\"get\" {{ form|safe }}>\n<input type=\"text\" {{ input|safe }}>\n</form>",
+    ext = "html",
+    remove_redundant_attributes = true
+)]
+#[derive(Template)]
+struct RedundantAttributesWithAskamaTemplate<'a> {
+    form: &'a str,
+    input: &'a str,
+}
+
+#[template_minify(
+    source = "<div>\n    <p>   a   </p>\n<!-- two\n lines -->\n</div>",
+    ext = "html",
+    preserve_lines = true
+)]
+#[derive(Template)]
+struct PreserveLinesTemplate;
+
+#[template_minify(
+    source = "<p>\n  {%- if show -%}\n  yes\n  {%- endif -%}\n</p>",
+    ext = "html",
+    preserve_lines = true
+)]
+#[derive(Template)]
+struct PreserveLinesWhitespaceControlTemplate {
+    show: bool,
+}
+
+#[template_minify(
+    source = "<style>\n  .a {\n    color: red; /* one\n two */\n  }\n</style>\n<script>\n  // note\n  const   a = 1; /* x\n y */\n</script>",
+    ext = "html",
+    preserve_lines = true
+)]
+#[derive(Template)]
+struct PreserveLinesAssetTemplate;
+
 #[test]
 fn preserves_template_syntax() {
     let rendered = TemplateSyntax { title: "ok" }.render().unwrap();
@@ -177,6 +459,30 @@ fn preserves_textarea_content() {
     assert!(rendered.contains("  value  \n  next  "));
 }
 
+#[test]
+fn preserves_nested_pre_content() {
+    let rendered = NestedPreTemplate.render().unwrap();
+
+    assert_eq!(
+        rendered,
+        "<pre>  outer  <pre>  inner  </pre>  still pre  </pre> <p> after </p>"
+    );
+}
+
+#[test]
+fn reads_markup_inside_textarea_as_text() {
+    let rendered = PreInsideTextareaTemplate.render().unwrap();
+
+    assert_eq!(rendered, "<textarea>  <pre>  a  </textarea> <p> after </p>");
+}
+
+#[test]
+fn keeps_stray_less_than_signs_as_text() {
+    let rendered = StrayLessThanTemplate.render().unwrap();
+
+    assert_eq!(rendered, "<p> a < b's value </p> <p> next </p>");
+}
+
 #[test]
 fn minifies_style_content() {
     let rendered = StyleTemplate.render().unwrap();
@@ -260,3 +566,266 @@ fn preserves_script_line_terminators_from_comments() {
     assert!(rendered.contains("return\n1"));
     assert!(rendered.contains("b\n++c"));
 }
+
+#[test]
+fn suppresses_whitespace_around_dash_markers() {
+    let rendered = SuppressWhitespaceTemplate { items: &["a", "b"] }
+        .render()
+        .unwrap();
+
+    assert_eq!(rendered, "<ul><li>a</li><li>b</li></ul>");
+}
+
+#[test]
+fn keeps_comments_between_dash_markers_and_whitespace() {
+    let after = CommentAfterDashMarkerTemplate.render().unwrap();
+    let before = CommentBeforeDashMarkerTemplate.render().unwrap();
+
+    assert_eq!(after, "<p>2<!-- c --> 1</p>");
+    assert_eq!(before, "<p>2 <!-- c -->1</p>");
+}
+
+#[test]
+fn preserves_whitespace_around_plus_markers() {
+    let rendered = PreserveWhitespaceTemplate { value: "x" }.render().unwrap();
+
+    assert_eq!(rendered, "<p>a   x   b</p>");
+}
+
+#[test]
+fn minimizes_whitespace_around_tilde_markers() {
+    let rendered = MinimizeWhitespaceTemplate { value: "x" }.render().unwrap();
+
+    assert_eq!(rendered, "<p>a\nx b</p>");
+}
+
+#[test]
+fn respects_whitespace_markers_inside_script_and_style() {
+    let script = ScriptPreserveWhitespaceTemplate { value: 1 }
+        .render()
+        .unwrap();
+    let style = StyleMinimizeWhitespaceTemplate { size: "1px" }
+        .render()
+        .unwrap();
+
+    assert_eq!(script, "<script>function value(){return 1;}</script>");
+    assert_eq!(style, "<style>.box{margin:\n1px}</style>");
+}
+
+#[test]
+fn applies_default_whitespace_argument() {
+    let minimized = DefaultMinimizeWhitespaceTemplate { value: "x" }
+        .render()
+        .unwrap();
+    let suppressed = DefaultSuppressWhitespaceTemplate { value: "x" }
+        .render()
+        .unwrap();
+
+    assert_eq!(minimized, "<p>\nx\n</p>");
+    assert_eq!(suppressed, "<p>x</p>");
+}
+
+#[test]
+fn keeps_delimiters_inside_string_literals() {
+    let expr = ExprDelimiterStringTemplate.render().unwrap();
+    let block = BlockDelimiterStringTemplate { value: "%}   %}" }
+        .render()
+        .unwrap();
+
+    assert_eq!(expr, "<p>}}   x</p>");
+    assert_eq!(block, "<p>yes</p>");
+}
+
+#[test]
+fn keeps_quotes_inside_char_literals() {
+    let rendered = CharLiteralTemplate { value: '"' }.render().unwrap();
+
+    assert_eq!(rendered, "<p>a b</p>");
+}
+
+#[test]
+fn skips_nested_askama_comments() {
+    let rendered = NestedCommentTemplate.render().unwrap();
+
+    assert_eq!(rendered, r#"<script>const value="a   b";</script>"#);
+}
+
+#[test]
+fn minifies_raw_blocks_as_plain_text() {
+    let rendered = RawBlockTemplate { value: "x" }.render().unwrap();
+
+    assert_eq!(rendered, r#"<div id="app"> {{- message }} </div><p> x</p>"#);
+}
+
+#[test]
+fn does_not_lex_askama_syntax_inside_raw_blocks() {
+    let rendered = RawUnbalancedTemplate { value: "x" }.render().unwrap();
+
+    assert_eq!(rendered, r#"<p>{{ " }}</p><p> x</p>"#);
+}
+
+#[test]
+fn minifies_script_and_style_inside_raw_blocks() {
+    let rendered = RawScriptStyleTemplate.render().unwrap();
+
+    assert_eq!(
+        rendered,
+        "<script>const text=`{{ a }}`;</script><style>.a{color:{{b}}}</style>"
+    );
+}
+
+#[test]
+fn strips_whitespace_around_control_tags_between_block_elements() {
+    let rendered = StripBlockWhitespaceTemplate {
+        items: &["a", "", "b"],
+    }
+    .render()
+    .unwrap();
+
+    assert_eq!(rendered, "<ul><li>a</li><li>b</li></ul>");
+}
+
+#[test]
+fn keeps_whitespace_around_control_tags_next_to_inline_content() {
+    let rendered = StripBlockWhitespaceInlineTemplate { show: true }
+        .render()
+        .unwrap();
+
+    assert_eq!(
+        rendered,
+        "<p> <b>a</b>  <i>b</i>  </p> <div>\n  <p>c</p></div>"
+    );
+}
+
+#[test]
+fn removes_whitespace_next_to_block_elements() {
+    let rendered = RemoveBlockWhitespaceTemplate { items: &["x", "y"] }
+        .render()
+        .unwrap();
+
+    assert_eq!(
+        rendered,
+        "<div><p>a <b> b </b></p><ul><li>x</li><li>y</li></ul></div>"
+    );
+}
+
+#[test]
+fn reads_block_and_inline_elements_from_config() {
+    let rendered = ConfiguredElementsTemplate.render().unwrap();
+
+    assert_eq!(
+        rendered,
+        "<app-card><span>a</span></app-card><ul><li>x</li> <li>y</li></ul>"
+    );
+}
+
+#[test]
+fn omits_optional_tags() {
+    let rendered = OptionalTagsTemplate { title: "Page" }.render().unwrap();
+
+    assert_eq!(
+        rendered,
+        "<!DOCTYPE html><title>Page</title><p>a<p>b<table><tr><td>1<td>2</table><a href=\"/\"><p>c</p></a>"
+    );
+}
+
+#[test]
+fn keeps_optional_tags_before_askama_tags() {
+    let rendered = OptionalTagsBeforeAskamaTemplate { items: &["x", "y"] }
+        .render()
+        .unwrap();
+
+    assert_eq!(rendered, "<ul><li>x</li><li>y</li></ul><ul><li>a<li>b</ul>");
+}
+
+#[test]
+fn keeps_whitespace_before_required_end_tags() {
+    let trailing = OptionalTagsTrailingSpaceTemplate.render().unwrap();
+    let pre = OptionalTagsBeforePreTemplate.render().unwrap();
+
+    assert_eq!(trailing, "<div>a </div>");
+    assert_eq!(pre, "<p>a</p> <pre>é </pre>");
+}
+
+#[test]
+fn removes_attribute_quotes() {
+    let rendered = AttributeQuotesTemplate {
+        id: 7,
+        attrs: "hidden",
+    }
+    .render()
+    .unwrap();
+
+    assert_eq!(
+        rendered,
+        "<a href=/home class=\"nav link\" title='Say \"hi\"' data-x='it&#39;s \"ok\"' id=\"7\" data-y=\"\">x</a> <br class=\"x\"/><input value=\"a\"hidden><img alt=b>"
+    );
+}
+
+#[test]
+fn simplifies_literal_attributes() {
+    let rendered = SimplifiedAttributesTemplate {
+        attrs: "hidden",
+        flag: true,
+    }
+    .render()
+    .unwrap();
+
+    assert_eq!(
+        rendered,
+        "<form> <input disabled required> <input type=\"checkbox\" checked hidden> <input/> <button disabled=\"true\" autofocus>x</button> <app-toggle disabled=\"disabled\"></app-toggle> <script>go()</script> <style>p{color:red}</style> <input type=\"text\"hidden> </form>"
+    );
+}
+
+#[test]
+fn keeps_block_tag_whitespace_inside_pre() {
+    let rendered = StripBlockWhitespaceInPreTemplate { items: &["x", "y"] }
+        .render()
+        .unwrap();
+
+    assert_eq!(
+        rendered,
+        "<pre>\n<div>a</div>\n\n<div>x</div>\n\n<div>y</div>\n\n</pre>"
+    );
+}
+
+#[test]
+fn keeps_default_attribute_values_next_to_askama_tags() {
+    let rendered = RedundantAttributesWithAskamaTemplate {
+        form: "method=\"post\"",
+        input: "type=\"password\"",
+    }
+    .render()
+    .unwrap();
+
+    assert_eq!(
+        rendered,
+        "<form method=\"get\" method=\"post\"> <input type=\"text\" type=\"password\"> </form>"
+    );
+}
+
+#[test]
+fn preserves_line_breaks_when_requested() {
+    let rendered = PreserveLinesTemplate.render().unwrap();
+
+    assert_eq!(rendered, "<div>\n<p> a </p>\n\n\n</div>");
+}
+
+#[test]
+fn leaves_whitespace_control_to_askama_when_preserving_lines() {
+    let rendered = PreserveLinesWhitespaceControlTemplate { show: true }
+        .render()
+        .unwrap();
+
+    assert_eq!(rendered, "<p>yes</p>");
+}
+
+#[test]
+fn preserves_line_breaks_in_script_and_style() {
+    let rendered = PreserveLinesAssetTemplate.render().unwrap();
+
+    assert_eq!(
+        rendered,
+        "<style>\n.a{\ncolor:red;\n\n}\n</style>\n<script>\n\nconst a=1;\n\n</script>"
+    );
+}
@@ -0,0 +1,6 @@
+use askama::Template;
+use askama_minify::template_minify;
+
+#[template_minify(source = "<div>   other   </div>", ext = "html")]
+#[derive(Template)]
+pub struct Page;
@@ -0,0 +1,26 @@
+use askama::Template;
+use askama_minify::template_minify;
+
+mod page;
+
+#[template_minify(
+    source = "<style>\n  p { color: red; }\n</style>\n<!-- note -->\n<p>   {{ title }}   </p>",
+    ext = "html"
+)]
+#[derive(Template)]
+struct Page<'a> {
+    title: &'a str,
+}
+
+#[template_minify(ext = "html")]
+#[derive(Template)]
+enum Pages {
+    #[template_minify(source = "<main>   home   </main>")]
+    Home,
+}
+
+fn main() {
+    _ = Page { title: "a" }.render();
+    _ = Pages::Home.render();
+    _ = page::Page.render();
+}
@@ -13,24 +13,198 @@ struct FileTemplate<'a> {
     title: &'a str,
 }
 
+#[template_minify(path = "tests/templates/basic.html", metadata = true)]
+#[derive(Template)]
+struct MetadataTemplate<'a> {
+    title: &'a str,
+}
+
 #[template_minify(path = "from_templates_dir.html")]
 #[derive(Template)]
 struct TemplatesDirTemplate<'a> {
     name: &'a str,
 }
 
+#[template_minify(path = "view.html", config = "tests/config/views.toml")]
+#[derive(Template)]
+struct ConfiguredDirsTemplate<'a> {
+    body: &'a str,
+}
+
 #[template_minify(path = "tests/templates/no_extension", ext = "html")]
 #[derive(Template)]
 struct ExtOverrideTemplate<'a> {
     value: &'a str,
 }
 
+#[template_minify(path = "tests/templates/include.html")]
+#[derive(Template)]
+struct IncludeTemplate<'a> {
+    title: &'a str,
+}
+
+#[template_minify(
+    source = "<p>\n    {%- include \"tests/templates/partials/item.html\" -%}\n</p>",
+    ext = "html"
+)]
+#[derive(Template)]
+struct IncludeWhitespaceControlTemplate;
+
+#[template_minify(
+    source = "{% include \"tests/templates/partials/head_open.html\" %}\n{% include \"tests/templates/partials/head_close.html\" %}",
+    ext = "html"
+)]
+#[derive(Template)]
+struct SplitScriptTemplate<'a> {
+    title: &'a str,
+}
+
+#[template_minify(
+    source = "{% raw %}{% include \"missing.html\" %}{% endraw %}",
+    ext = "html"
+)]
+#[derive(Template)]
+struct RawIncludeTemplate;
+
+#[template_minify(path = "tests/templates/layout/page.html", flatten_extends = true)]
+#[derive(Template)]
+struct FlattenedTemplate<'a> {
+    title: &'a str,
+}
+
+#[template_minify(path = "tests/templates/import.html")]
+#[derive(Template)]
+struct ImportTemplate<'a> {
+    title: &'a str,
+}
+
+#[template_minify(source = "<div>   default   </div>", ext = "html")]
+#[derive(Template)]
+enum EnumTemplate<'a> {
+    #[template_minify(source = "<p>   {{ title }}   </p>")]
+    Inline {
+        title: &'a str,
+    },
+    #[template_minify(path = "tests/templates/basic.html")]
+    File {
+        title: &'a str,
+    },
+    Default,
+}
+
+/// Greets the user.
+///
+/// ```askama
+/// <p>
+///     Hello   {{ name }}
+/// </p>
+/// ```
+#[template_minify(in_doc = true, ext = "html")]
+#[derive(Template)]
+struct DocTemplate<'a> {
+    name: &'a str,
+}
+
+#[template_minify(ext = "html")]
+#[derive(Template)]
+enum DocEnumTemplate {
+    /// ```jinja
+    /// <b>   documented   </b>
+    /// ```
+    #[template_minify(in_doc = true)]
+    Documented,
+}
+
+#[template_minify(ext = "html", escape = "none")]
+#[derive(Template)]
+enum VariantOnlyTemplate<'a> {
+    #[template_minify(source = "<b>   {{ value }}   </b>")]
+    Bold { value: &'a str },
+}
+
+#[template_minify(escape = "none")]
+#[derive(Template)]
+#[template(path = "tests/templates/basic.html", print = "none")]
+struct RewrittenTemplate<'a> {
+    title: &'a str,
+}
+
+#[template_minify(strip_block_whitespace = true)]
+#[derive(Template)]
+#[cfg_attr(
+    all(),
+    template(
+        source = "<ul>\n  {% if show %}\n  <li>   a   </li>\n  {% endif %}\n</ul>",
+        ext = "html"
+    )
+)]
+#[cfg_attr(any(), template(source = "unused", ext = "txt"))]
+struct CfgAttrTemplate {
+    show: bool,
+}
+
+#[template_minify]
+#[derive(Template)]
+#[cfg_attr(any(), template(path = "tests/templates/missing.html"))]
+#[cfg_attr(all(), template(source = "<p>   active   </p>", ext = "html"))]
+struct InactiveCfgAttrTemplate;
+
+#[template_minify]
+#[derive(Debug)]
+#[cfg_attr(
+    any(),
+    derive(Template),
+    template(source = "<p>   inactive   </p>", ext = "html")
+)]
+struct InactiveOnlyCfgAttrTemplate;
+
+#[template_minify]
+#[derive(Template)]
+#[template(ext = "html")]
+enum RewrittenEnumTemplate<'a> {
+    #[template(source = "<p>   {{ title }}   </p>")]
+    Inline { title: &'a str },
+}
+
+#[template_minify(
+    source = "<section>\n    <p>{{ text }}</p>\n</section>",
+    ext = "html",
+    config = "tests/config/budget.toml",
+    max_size = 64
+)]
+#[derive(Template)]
+struct BudgetTemplate<'a> {
+    text: &'a str,
+}
+
 #[template_minify(source = "<div>{{ raw }}</div>", ext = "html", escape = "none")]
 #[derive(Template)]
 struct EscapeNoneTemplate<'a> {
     raw: &'a str,
 }
 
+#[template_minify(
+    source = "<div>\n  [% if show %]\n    <p>[[ \"a   <b>\" ]]</p>\n  [% endif %]\n  [# a   comment #]\n</div>",
+    ext = "html",
+    escape = "none",
+    config = "tests/config/syntax.toml",
+    syntax = "square"
+)]
+#[derive(Template)]
+struct CustomSyntaxTemplate {
+    show: bool,
+}
+
+#[template_minify(
+    source = "<script>\n  const   value = [[ value ]];\n</script>",
+    ext = "html",
+    config = "tests/config/default_syntax.toml"
+)]
+#[derive(Template)]
+struct DefaultCustomSyntaxTemplate {
+    value: i32,
+}
+
 #[template_minify(source = "line 1\n  {{ value }}\nline 3", ext = "txt")]
 #[derive(Template)]
 struct TextTemplate<'a> {
@@ -74,12 +248,146 @@ fn resolves_paths_from_templates_directory() {
     assert_eq!(rendered, "<main> <p>Ada</p> </main>");
 }
 
+#[test]
+fn resolves_paths_from_configured_directories() {
+    let rendered = ConfiguredDirsTemplate { body: "text" }.render().unwrap();
+    assert_eq!(rendered, "<article> <p>text</p> </article>");
+}
+
 #[test]
 fn supports_extension_override_for_extensionless_files() {
     let rendered = ExtOverrideTemplate { value: "42" }.render().unwrap();
     assert_eq!(rendered, "<p>42</p>");
 }
 
+#[test]
+fn inlines_and_minifies_included_templates() {
+    let rendered = IncludeTemplate { title: "Home" }.render().unwrap();
+    assert_eq!(
+        rendered,
+        r#"<header> <nav> <a href="/">Home</a> <span> item </span> </nav> </header>"#
+    );
+}
+
+#[test]
+fn keeps_include_whitespace_control() {
+    let rendered = IncludeWhitespaceControlTemplate.render().unwrap();
+    assert_eq!(rendered, "<p><span> item </span></p>");
+}
+
+#[test]
+fn checks_elements_split_across_includes() {
+    let rendered = SplitScriptTemplate { title: "Home" }.render().unwrap();
+    assert_eq!(
+        rendered,
+        "<head> <script>const title=\"Home\";\ndocument.title=title;</script> </head>"
+    );
+}
+
+#[test]
+fn leaves_includes_inside_raw_blocks() {
+    let rendered = RawIncludeTemplate.render().unwrap();
+    assert_eq!(rendered, r#"{% include "missing.html" %}"#);
+}
+
+#[test]
+fn minifies_enum_variant_templates() {
+    let inline = EnumTemplate::Inline { title: "Home" }.render().unwrap();
+    let file = EnumTemplate::File { title: "Home" }.render().unwrap();
+    let default = EnumTemplate::Default.render().unwrap();
+
+    assert_eq!(inline, "<p> Home </p>");
+    assert_eq!(file, "<section> <h1>Home</h1> </section>");
+    assert_eq!(default, "<div> default </div>");
+
+    let bold = VariantOnlyTemplate::Bold { value: "<i>" }.render().unwrap();
+    assert_eq!(bold, "<b> <i> </b>");
+}
+
+#[test]
+fn rewrites_existing_template_attributes() {
+    let rendered = RewrittenTemplate { title: "<b>" }.render().unwrap();
+    let cfg_attr = CfgAttrTemplate { show: true }.render().unwrap();
+    let variant = RewrittenEnumTemplate::Inline { title: "Home" }
+        .render()
+        .unwrap();
+
+    assert_eq!(rendered, "<section> <h1><b></h1> </section>");
+    assert_eq!(cfg_attr, "<ul><li> a </li></ul>");
+    assert_eq!(variant, "<p> Home </p>");
+}
+
+#[test]
+fn skips_templates_behind_inactive_cfg_attr() {
+    let rendered = InactiveCfgAttrTemplate.render().unwrap();
+
+    assert_eq!(rendered, "<p> active </p>");
+}
+
+#[test]
+fn keeps_items_whose_templates_are_all_inactive() {
+    let item = format!("{InactiveOnlyCfgAttrTemplate:?}");
+
+    assert_eq!(item, "InactiveOnlyCfgAttrTemplate");
+}
+
+#[test]
+fn minifies_templates_from_doc_comments() {
+    let rendered = DocTemplate { name: "Ann" }.render().unwrap();
+    let variant = DocEnumTemplate::Documented.render().unwrap();
+
+    assert_eq!(rendered, "<p> Hello Ann </p>");
+    assert_eq!(variant, "<b> documented </b>");
+}
+
+#[test]
+fn flattens_extends_chains() {
+    let rendered = FlattenedTemplate { title: "Home" }.render().unwrap();
+    assert_eq!(
+        rendered,
+        "<!DOCTYPE html> <html> <head> <title>Home | Section - Site</title> </head> <body>  <section> <ul>  <li>Home</li>  </ul> </section>  </body> </html>"
+    );
+}
+
+#[test]
+fn inlines_and_minifies_imported_macros() {
+    let rendered = ImportTemplate { title: "Hi" }.render().unwrap();
+    assert_eq!(
+        rendered,
+        r#" <main>  <div class="card">  <button class="btn">  <i class="star"></i>  Hi </button>  </div>  </main>"#
+    );
+}
+
+#[test]
+fn exposes_template_metadata() {
+    let rendered = MetadataTemplate { title: "Hello" }.render().unwrap();
+
+    assert_eq!(rendered, "<section> <h1>Hello</h1> </section>");
+    assert_eq!(
+        MetadataTemplate::MINIFIED_SOURCE,
+        "<section> <h1>{{ title }}</h1> </section>"
+    );
+    assert_eq!(
+        MetadataTemplate::MINIFIED_LEN,
+        MetadataTemplate::MINIFIED_SOURCE.len()
+    );
+    assert_eq!(
+        MetadataTemplate::ORIGINAL_LEN,
+        include_str!("templates/basic.html").len()
+    );
+    assert_eq!(
+        MetadataTemplate::SOURCE_PATH,
+        Some("tests/templates/basic.html")
+    );
+}
+
+#[test]
+fn overrides_configured_size_budget() {
+    let rendered = BudgetTemplate { text: "ok" }.render().unwrap();
+
+    assert_eq!(rendered, "<section> <p>ok</p> </section>");
+}
+
 #[test]
 fn forwards_askama_template_arguments() {
     let rendered = EscapeNoneTemplate {
@@ -91,6 +399,18 @@ fn forwards_askama_template_arguments() {
     assert_eq!(rendered, "<div><strong>trusted</strong></div>");
 }
 
+#[test]
+fn uses_selected_custom_syntax() {
+    let rendered = CustomSyntaxTemplate { show: true }.render().unwrap();
+    assert_eq!(rendered, "<div>  <p>a   <b></p>   </div>");
+}
+
+#[test]
+fn uses_configured_default_syntax() {
+    let rendered = DefaultCustomSyntaxTemplate { value: 7 }.render().unwrap();
+    assert_eq!(rendered, "<script>const value=7;</script>");
+}
+
 #[test]
 fn leaves_non_html_templates_unminified() {
     let rendered = TextTemplate { value: "ok" }.render().unwrap();
@@ -0,0 +1,72 @@
+mod common;
+
+use serde_json::{Value, json};
+use std::fs;
+
+#[test]
+fn reports_minified_templates() {
+    let root = common::build_with_dir("ASKAMA_MINIFY_REPORT_DIR");
+
+    let mut reports = Vec::new();
+    let mut dirs = vec![root.clone()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else {
+                let report: Value =
+                    serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+                reports.push((path, report));
+            }
+        }
+    }
+    reports.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    assert_eq!(reports.len(), 3);
+    let (path, other) = &reports[0];
+    assert!(path.ends_with("tests/output/page/Page.json"));
+    assert!(
+        other["rust_file"]
+            .as_str()
+            .unwrap()
+            .ends_with("tests/output/page.rs")
+    );
+    assert_eq!(other["css_backend"], Value::Null);
+    let (path, page) = &reports[1];
+    assert!(path.ends_with("tests/output/templates/Page.json"));
+    assert!(
+        page["rust_file"]
+            .as_str()
+            .unwrap()
+            .ends_with("tests/output/templates.rs")
+    );
+    assert_eq!(
+        page,
+        &json!({
+            "rust_file": page["rust_file"],
+            "item": "Page",
+            "source": "<source attribute>",
+            "ext": "html",
+            "original_bytes": 75,
+            "minified_bytes": 48,
+            "saved": {
+                "html": 5,
+                "css": 9,
+                "js": 0,
+                "comments": 13,
+            },
+            "css_backend": if cfg!(feature = "advanced-css") {
+                "lightningcss"
+            } else {
+                "conservative"
+            },
+        })
+    );
+    let (path, home) = &reports[2];
+    assert!(path.ends_with("tests/output/templates/Pages.Home.json"));
+    assert_eq!(home["item"], "Pages::Home");
+    assert_eq!(home["minified_bytes"], 19);
+
+    fs::remove_dir_all(root).unwrap();
+}
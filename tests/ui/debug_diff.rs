@@ -0,0 +1,16 @@
+#![deny(deprecated)]
+
+use askama::Template;
+use askama_minify::template_minify;
+
+#[template_minify(
+    source = "<ul>\n    <li>   {{ item }}   </li>\n</ul>",
+    ext = "html",
+    debug = "diff"
+)]
+#[derive(Template)]
+struct DebugDiff<'a> {
+    item: &'a str,
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use askama::Template;
+use askama_minify::template_minify;
+
+#[template_minify(
+    source = "<script>const quoted = /\"[^\"]*\"/g;</script>",
+    ext = "html",
+    deny_warnings = true
+)]
+#[derive(Template)]
+struct DenyWarnings;
+
+fn main() {}
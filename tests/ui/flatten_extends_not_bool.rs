@@ -0,0 +1,8 @@
+use askama::Template;
+use askama_minify::template_minify;
+
+#[template_minify(source = "<p></p>", ext = "html", flatten_extends = "yes")]
+#[derive(Template)]
+struct FlattenExtendsNotBool;
+
+fn main() {}
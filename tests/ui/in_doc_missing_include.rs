@@ -0,0 +1,11 @@
+use askama::Template;
+use askama_minify::template_minify;
+
+/// ```askama
+/// <p>{% include "missing.html" %}</p>
+/// ```
+#[template_minify(in_doc = true, ext = "html")]
+#[derive(Template)]
+struct InDocMissingInclude;
+
+fn main() {}
@@ -0,0 +1,9 @@
+use askama::Template;
+use askama_minify::template_minify;
+
+/// No template here.
+#[template_minify(in_doc = true, ext = "html")]
+#[derive(Template)]
+struct InDocWithoutCodeBlock;
+
+fn main() {}
@@ -0,0 +1,24 @@
+use askama::Template;
+use askama_minify::template_minify;
+
+#[template_minify(
+    source = r#"
+        <style>
+            .card { padding: 1rem; border: 1px solid {{ border }}; }
+        </style>
+        <div class="card">{{ title }}</div>
+        <script>
+            const cards = document.querySelectorAll(".card");
+            cards.forEach((card) => card.classList.add("ready"));
+        </script>
+    "#,
+    ext = "html",
+    max_size = 128
+)]
+#[derive(Template)]
+struct MaxSize<'a> {
+    title: &'a str,
+    border: &'a str,
+}
+
+fn main() {}
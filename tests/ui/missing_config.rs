@@ -0,0 +1,8 @@
+use askama::Template;
+use askama_minify::template_minify;
+
+#[template_minify(path = "index.html", config = "missing.toml")]
+#[derive(Template)]
+struct MissingConfig;
+
+fn main() {}
@@ -0,0 +1,8 @@
+use askama::Template;
+use askama_minify::template_minify;
+
+#[template_minify(source = r#"<div>{% include "missing.html" %}</div>"#, ext = "html")]
+#[derive(Template)]
+struct MissingInclude;
+
+fn main() {}
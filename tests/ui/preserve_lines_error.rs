@@ -0,0 +1,14 @@
+use askama::Template;
+use askama_minify::template_minify;
+
+#[template_minify(
+    source = "<div>\n    <p>\n        {{ value }\n    </p>\n</div>",
+    ext = "html",
+    preserve_lines = true
+)]
+#[derive(Template)]
+struct PreserveLinesError<'a> {
+    value: &'a str,
+}
+
+fn main() {}
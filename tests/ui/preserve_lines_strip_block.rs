@@ -0,0 +1,14 @@
+use askama::Template;
+use askama_minify::template_minify;
+
+#[template_minify(
+    source = "<ul>\n  {% if true %}\n  <li>a</li>\n  {% endif %}\n</ul>",
+    ext = "html",
+    preserve_lines = true,
+    strip_block_whitespace = true,
+    deny_warnings = true
+)]
+#[derive(Template)]
+struct PreserveLinesStripBlock;
+
+fn main() {}
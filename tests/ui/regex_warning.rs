@@ -0,0 +1,13 @@
+#![deny(deprecated)]
+
+use askama::Template;
+use askama_minify::template_minify;
+
+#[template_minify(
+    source = "<script>const words = text.split(/ +/);</script>",
+    ext = "html"
+)]
+#[derive(Template)]
+struct RegexWarning;
+
+fn main() {}
@@ -0,0 +1,11 @@
+use askama::Template;
+use askama_minify::template_minify;
+
+#[template_minify(
+    source = "<main>\n  <script>const a = \"{{ \"</script>\" }}\";\n</main>",
+    ext = "html"
+)]
+#[derive(Template)]
+struct UnclosedScript;
+
+fn main() {}
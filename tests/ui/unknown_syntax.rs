@@ -0,0 +1,8 @@
+use askama::Template;
+use askama_minify::template_minify;
+
+#[template_minify(source = "<p></p>", ext = "html", syntax = "square")]
+#[derive(Template)]
+struct UnknownSyntax;
+
+fn main() {}
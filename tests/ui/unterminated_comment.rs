@@ -0,0 +1,10 @@
+use askama::Template;
+use askama_minify::template_minify;
+
+#[template_minify(source = "<div>\n  <!-- {{ value }} -->\n  <!-- open\n</div>", ext = "html")]
+#[derive(Template)]
+struct UnterminatedComment<'a> {
+    value: &'a str,
+}
+
+fn main() {}
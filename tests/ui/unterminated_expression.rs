@@ -0,0 +1,10 @@
+use askama::Template;
+use askama_minify::template_minify;
+
+#[template_minify(source = "<div>\n  <p>{{ value </p>\n</div>", ext = "html")]
+#[derive(Template)]
+struct UnterminatedExpression<'a> {
+    value: &'a str,
+}
+
+fn main() {}
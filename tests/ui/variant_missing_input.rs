@@ -0,0 +1,11 @@
+use askama::Template;
+use askama_minify::template_minify;
+
+#[template_minify(ext = "html")]
+#[derive(Template)]
+enum VariantMissingInput {
+    #[template_minify(escape = "none")]
+    Empty,
+}
+
+fn main() {}